@@ -0,0 +1,68 @@
+use std::str::FromStr;
+
+/// A number of spots kept free for some customer classes, as specified on the command line.
+///
+/// The format is `SPOTS[,for=CLASS/CLASS/...]`.
+/// Without any classes, the spots are kept for the classes with a priority above 0.
+#[derive(Clone)]
+pub struct ReserveSpec {
+    pub spots: usize,
+    pub classes: Vec<String>,
+}
+
+impl FromStr for ReserveSpec {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parts = s.split(',');
+        let spots = parts.next().unwrap_or_default();
+        let mut spec = Self {
+            spots: spots
+                .parse()
+                .map_err(|e| format!("invalid number of spots `{spots}`: {e}"))?,
+            classes: Vec::new(),
+        };
+
+        for part in parts {
+            match part.split_once('=') {
+                Some(("for", value)) => {
+                    spec.classes = value.split('/').map(String::from).collect();
+                }
+                _ => return Err(format!("unknown reserve option `{part}`")),
+            }
+        }
+
+        Ok(spec)
+    }
+}
+
+/// A policy that stops admitting cars before the lot is physically full.
+#[derive(Clone)]
+pub struct Admission {
+    /// The space kept free for the reserved classes, in units of space.
+    pub reserve: usize,
+    /// Whether each customer class can use the reserved space.
+    pub reserved_for: Vec<bool>,
+    /// The occupancy (as a fraction of the capacity) at which the "LOT FULL" sign turns on, if it ever does.
+    /// No cars are admitted while the sign is on.
+    pub full_sign: Option<f32>,
+}
+
+impl Admission {
+    /// Whether the sign is on at a given occupancy.
+    #[inline]
+    pub fn sign_on(&self, occupancy: f32) -> bool {
+        self.full_sign
+            .is_some_and(|threshold| occupancy >= threshold)
+    }
+
+    /// Whether a car of a class taking up some units of space is let in,
+    /// given the free space in the pools that it can use and the occupancy of the lot.
+    #[inline]
+    pub fn admits(&self, class: usize, units: usize, free: usize, occupancy: f32) -> bool {
+        if self.sign_on(occupancy) {
+            return false;
+        }
+        self.reserved_for[class] || free >= units.saturating_add(self.reserve)
+    }
+}
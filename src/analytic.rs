@@ -0,0 +1,273 @@
+use crate::{
+    customer_class::{CustomerClass, DAY},
+    scenario::Scenario,
+};
+
+/// The offered load of a scenario, i.e. the average number of cars that would be parked in a lot
+/// with unlimited capacity (λ·E[S], summed over every class and averaged over the day).
+/// Each class only counts for the part of the day that it arrives in (its hours, while the lot is open).
+/// Classes that leave at a time of day stay until their mean departure time,
+/// and special events are left out, since their arrivals aren't steady.
+pub fn offered_load(scenario: &Scenario) -> f64 {
+    scenario
+        .classes
+        .iter()
+        .map(|class| f64::from(class.cars_per_second) * daily_stay(scenario, class))
+        .sum()
+}
+
+/// The interval at which the day is sampled, which is more than precise enough for hours of the day.
+const SAMPLE_STEP: u32 = 60;
+
+/// The sampled seconds of the day at which cars of a class arrive (within its hours, while the lot is open).
+fn arrival_times<'a>(
+    scenario: &'a Scenario,
+    class: &'a CustomerClass,
+) -> impl Iterator<Item = u32> + 'a {
+    (0..DAY).step_by(SAMPLE_STEP as usize).filter(|&time| {
+        class.arrives_at(time)
+            && scenario
+                .opening_hours
+                .is_none_or(|hours| hours.is_open(time))
+    })
+}
+
+/// The total stay of a class's cars arriving at each second of the day, divided by the length of the day,
+/// so that multiplying it by the class's arrival rate gives the class's offered load.
+fn daily_stay(scenario: &Scenario, class: &CustomerClass) -> f64 {
+    let stay = f64::from(class.stay.mean());
+    let total = arrival_times(scenario, class)
+        .map(|time| {
+            class.departure.map_or(stay, |departure| {
+                let leave = departure.mean() as u32 % DAY;
+                f64::from((leave + DAY - time - 1) % DAY + 1)
+            })
+        })
+        .sum::<f64>();
+    total * f64::from(SAMPLE_STEP) / f64::from(DAY)
+}
+
+/// The arrival rate averaged over the day, summed over every class.
+fn arrival_rate(scenario: &Scenario) -> f64 {
+    scenario
+        .classes
+        .iter()
+        .map(|class| {
+            let arriving = arrival_times(scenario, class).count() as f64;
+            f64::from(class.cars_per_second) * arriving * f64::from(SAMPLE_STEP) / f64::from(DAY)
+        })
+        .sum()
+}
+
+/// A capacity that is all but certain to hold every car that arrives during a run at once,
+/// including any cars parked at the start, used to bound the search for a capacity.
+pub fn capacity_limit(scenario: &Scenario) -> usize {
+    let days = scenario.steps.div_ceil(DAY);
+    let events = scenario
+        .events
+        .iter()
+        .map(|(_, event)| {
+            let occurrences = (0..days)
+                .filter(|day| event.days & (1 << (day % 7)) != 0)
+                .count();
+            f64::from(event.cars) * occurrences as f64
+        })
+        .sum::<f64>();
+    let cars = arrival_rate(scenario) * f64::from(scenario.steps) + events + offered_load(scenario);
+    // The number of cars is random, so allow for it to be well above the mean
+    (cars + 10.0 * cars.sqrt()).ceil() as usize + 1
+}
+
+/// The smallest number of spots that holds the demand of an M/G/∞ queue with a given load
+/// at least a given fraction of the time.
+/// With Poisson arrivals and independent stays, the number of cars in a lot with unlimited capacity
+/// is Poisson distributed with a mean of the load, whatever the distribution of the stays,
+/// so this is the quantile of that Poisson distribution.
+pub fn mg_inf_spots(load: f64, fraction: f64) -> usize {
+    // Sum the probabilities in log space, since e^-load underflows for large loads
+    let mut log_p = -load;
+    let mut cumulative = log_p.exp();
+    let mut spots = 0;
+    while cumulative < fraction && spots < u32::MAX as usize {
+        spots += 1;
+        log_p += load.ln() - (spots as f64).ln();
+        cumulative += log_p.exp();
+        // Past the mean, the remaining probability is negligible once the terms vanish
+        if spots as f64 > load && log_p < -50.0 {
+            break;
+        }
+    }
+    spots
+}
+
+/// The probability that a car finds every one of `spots` spots taken in a loss system (Erlang B).
+pub fn erlang_b(spots: usize, load: f64) -> f64 {
+    // B(0) = 1, B(c) = a·B(c-1) / (c + a·B(c-1)), which is numerically stable
+    (1..=spots).fold(1.0, |blocking, c| {
+        load * blocking / (c as f64 + load * blocking)
+    })
+}
+
+/// The probability that a car has to wait in a queueing system with `spots` spots (Erlang C),
+/// or `None` if the queue grows without bound.
+/// This is only exact for exponential stays (the M/M/c queue).
+pub fn erlang_c(spots: usize, load: f64) -> Option<f64> {
+    let c = spots as f64;
+    if load >= c {
+        return None;
+    }
+    let blocking = erlang_b(spots, load);
+    Some(c * blocking / (c - load * (1.0 - blocking)))
+}
+
+/// The mean time that cars spend in the queue in an M/M/c queue with `spots` spots,
+/// given the mean stay, or `None` if the queue grows without bound.
+pub fn mean_wait(spots: usize, load: f64, mean_stay: f64) -> Option<f64> {
+    erlang_c(spots, load).map(|waiting| waiting * mean_stay / (spots as f64 - load))
+}
+
+/// An analytical estimate of the capacity needed, used as a starting point for searching.
+/// None of the formulas account for arrival rates that change with the time of day or for the start-up period,
+/// so this is only a rough guide for the simulated answer.
+pub struct Estimate {
+    /// The offered load (see [`offered_load`]).
+    pub load: f64,
+    /// The number of spots that holds the demand of the M/G/∞ queue 99.9% of the time.
+    pub spots: usize,
+}
+
+impl Estimate {
+    /// The fraction of the time that [`Self::spots`] has to hold the demand for.
+    pub const COVERAGE: f64 = 0.999;
+
+    pub fn new(scenario: &Scenario) -> Self {
+        let load = offered_load(scenario);
+        Self {
+            load,
+            spots: mg_inf_spots(load, Self::COVERAGE).max(1),
+        }
+    }
+
+    /// The mean stay over every class, weighted by arrival rate.
+    fn mean_stay(scenario: &Scenario) -> f64 {
+        offered_load(scenario) / arrival_rate(scenario).max(f64::MIN_POSITIVE)
+    }
+
+    /// Prints the estimate, and the Erlang B and C figures for a given number of spots.
+    pub fn print(&self, scenario: &Scenario, spots: usize) {
+        eprint!(
+            "\nThe analytic estimate: {} spots ({:.1} cars parked on average in an unlimited lot, {:.1}% of the time)",
+            self.spots,
+            self.load,
+            Self::COVERAGE * 100.0
+        );
+        eprint!(
+            "\nWith {spots} spots, Erlang B blocks {:.2}% of cars",
+            erlang_b(spots, self.load) * 100.0
+        );
+        if let (Some(waiting), Some(wait)) = (
+            erlang_c(spots, self.load),
+            mean_wait(spots, self.load, Self::mean_stay(scenario)),
+        ) {
+            eprint!(
+                " and Erlang C makes {:.2}% wait ({wait:.1} seconds on average)",
+                waiting * 100.0
+            );
+        } else {
+            eprint!(" and Erlang C has the queue growing without bound");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use clap::Parser;
+    use rand::{SeedableRng, rngs::StdRng};
+
+    use super::*;
+    use crate::{cli::Cli, event_simulator::EventSimulator, simulator::Simulator};
+
+    /// Whether two numbers are within a fraction of each other.
+    fn close(actual: f64, expected: f64, tolerance: f64) -> bool {
+        (actual - expected).abs() <= tolerance * expected
+    }
+
+    #[test]
+    fn erlang_b_matches_known_values() {
+        assert!((erlang_b(0, 5.0) - 1.0).abs() < 1e-12);
+        assert!((erlang_b(2, 1.0) - 0.2).abs() < 1e-12);
+        assert!((erlang_b(10, 5.0) - 0.018_384_570).abs() < 1e-8);
+    }
+
+    #[test]
+    fn erlang_c_matches_known_values() {
+        assert!((erlang_c(2, 1.0).unwrap() - 1.0 / 3.0).abs() < 1e-12);
+        assert!((erlang_c(10, 5.0).unwrap() - 0.036_105_359).abs() < 1e-8);
+        assert_eq!(erlang_c(5, 5.0), None);
+    }
+
+    #[test]
+    fn mg_inf_spots_is_the_poisson_quantile() {
+        assert_eq!(mg_inf_spots(1.0, 0.5), 1);
+        assert_eq!(mg_inf_spots(10.0, 0.999), 21);
+        assert_eq!(mg_inf_spots(100.0, 0.999), 132);
+        // e^-load underflows, but the quantile is still about 3.1 standard deviations above the mean
+        assert!((20_430..=20_445).contains(&mg_inf_spots(20_000.0, 0.999)));
+    }
+
+    #[test]
+    fn offered_load_only_counts_the_hours_that_a_class_arrives() {
+        let cli = Cli::parse_from([
+            "test",
+            "0",
+            "-e",
+            "--class",
+            "day,rate=60,stay=0/1800/3600,hours=6-18",
+        ]);
+        let scenario = Scenario::new(&cli).unwrap();
+        // Half of the day at one car a minute, staying for half an hour on average
+        assert!(close(offered_load(&scenario), 15.0, 0.01));
+    }
+
+    /// Simulates a lot with exponential stays for 60 days, returning its statistics.
+    fn simulate(spots: usize, args: &[&str]) -> crate::report::RunStats {
+        let mut cli = vec![
+            "test",
+            "60",
+            "-e",
+            "--exponential-stay",
+            "-m",
+            "3600",
+            "-d",
+            "5184000",
+        ];
+        cli.extend(args);
+        let scenario = Scenario::new(&Cli::parse_from(cli)).unwrap();
+        let mut rng = StdRng::seed_from_u64(1);
+        let mut sim = EventSimulator::new(spots, &scenario, &mut rng);
+        sim.simulate(&mut rng);
+        sim.stats()
+    }
+
+    #[test]
+    fn simulated_blocking_matches_erlang_b() {
+        // Drivers who find the lot full leave right away, which makes the lot a loss system
+        let stats = simulate(35, &["--balk-when-full", "1"]);
+        let class = &stats.classes[0];
+        let blocking = class.balked as f64 / class.arrived as f64;
+        // 60 cars an hour staying for half an hour on average
+        assert!(
+            close(blocking, erlang_b(35, 30.0), 0.1),
+            "blocked {blocking}"
+        );
+    }
+
+    #[test]
+    fn simulated_wait_matches_erlang_c() {
+        let stats = simulate(35, &[]);
+        let class = &stats.classes[0];
+        let wait = class.wait_time as f64 / class.parked as f64;
+        let expected = mean_wait(35, 30.0, 1800.0).unwrap();
+        assert!(close(wait, expected, 0.15), "waited {wait} seconds");
+    }
+}
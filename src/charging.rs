@@ -0,0 +1,82 @@
+use clap::ValueEnum;
+
+use crate::{customer_class::parse_triangular, triangular_distribution::TriangularPdfSampler};
+
+/// What an EV does once it is done charging (and idling) at a charger.
+#[derive(Clone, Copy, ValueEnum)]
+pub enum AfterCharging {
+    /// The driver leaves the site, so the charging session is the whole visit.
+    Leave,
+    /// The driver moves the car to a regular spot for the rest of their stay.
+    /// If there is no spot to move to, the car stays plugged in until the driver leaves.
+    Move,
+}
+
+/// The charging needs of an electric vehicle type.
+#[derive(Clone)]
+pub struct Charging {
+    /// The usable battery capacity, in kWh.
+    pub battery: f32,
+    /// The state of charge on arrival, as a percentage.
+    pub soc: TriangularPdfSampler,
+    /// The state of charge that the driver charges up to, as a percentage.
+    pub target: f32,
+    /// The time that the car stays plugged in after it is done charging, if any.
+    pub idle: Option<TriangularPdfSampler>,
+    pub after: AfterCharging,
+}
+
+/// A single charging session.
+pub struct Session {
+    /// The number of seconds spent charging.
+    pub charge_time: u32,
+    /// The number of seconds until the car is unplugged, including any idle time.
+    pub plugged_in: u32,
+    /// The energy delivered, in Wh.
+    pub energy: u32,
+}
+
+impl Charging {
+    /// Creates the charging needs of a vehicle with a given battery and the default behaviour.
+    pub fn new(battery: f32) -> Self {
+        Self {
+            battery,
+            soc: TriangularPdfSampler::new(10.0, 30.0, 60.0),
+            target: 80.0,
+            idle: None,
+            after: AfterCharging::Leave,
+        }
+    }
+
+    /// Samples a charging session at a charger with a given power, in kW.
+    /// Charging is assumed to happen at the full power of the charger the whole time.
+    pub fn session<T: rand::Rng>(&self, rng: &mut T, power: f32) -> Session {
+        let soc = self.soc.sample(rng) as f32;
+        let energy = self.battery * (self.target - soc).max(0.0) / 100.0;
+        let charge_time = (energy / power * 3600.0).ceil() as u32;
+        let idle = self.idle.map_or(0, |idle| idle.sample(rng));
+        Session {
+            charge_time,
+            plugged_in: (charge_time + idle).max(1),
+            energy: (energy * 1000.0) as u32,
+        }
+    }
+
+    /// Parses a single charging option of a vehicle spec.
+    /// Returns `Ok(false)` if the key isn't a charging option.
+    pub fn parse_option(&mut self, key: &str, value: &str) -> Result<bool, String> {
+        match key {
+            "soc" => self.soc = parse_triangular(value)?,
+            "target" => {
+                self.target = match value.parse::<f32>() {
+                    Ok(target) if (0.0..=100.0).contains(&target) => target,
+                    _ => return Err(format!("invalid target `{value}`")),
+                };
+            }
+            "idle" => self.idle = Some(parse_triangular(value)?),
+            "after" => self.after = AfterCharging::from_str(value, true)?,
+            _ => return Ok(false),
+        }
+        Ok(true)
+    }
+}
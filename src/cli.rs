@@ -1,10 +1,33 @@
-use clap::Parser;
+use std::str::FromStr;
+
+use clap::{ArgGroup, Parser};
+
+use crate::{
+    admission::ReserveSpec,
+    customer_class::{ClassSpec, parse_hours},
+    gate::ServiceTime,
+    layout::{Layout, SpotChoice},
+    network::Routing,
+    opening_hours::AtClosing,
+    parking_lot::PoolSpec,
+    pricing::SurgePricing,
+    queue::Overflow,
+    queue::QueueDiscipline,
+    schedule::ClosureSpec,
+    special_event::EventSpec,
+    tariff::Tariff,
+    vehicle::VehicleSpec,
+    warm_up::InitialOccupancy,
+    warm_up::WarmUp,
+};
 
 #[allow(clippy::struct_excessive_bools)]
 #[derive(Parser)]
+#[command(group(ArgGroup::new("admission").args(["reserve", "full_sign"]).multiple(true)))]
+#[command(group(ArgGroup::new("continuous_modes").args(["continuous", "continuous_heap", "event_based"]).multiple(true)))]
 #[command(version, about = "A Rust reimplementation of one of my assignments.", long_about = None)]
 pub struct Cli {
-    /// The number of cars that should enter the lot per hour. Must be positive.
+    /// The number of cars that should enter the lot per hour. Must be positive, unless --class is used.
     pub cars_per_hour: f32,
 
     /// The number of runs to do per capacity. More runs will take longer but produce more stable results.
@@ -35,14 +58,32 @@ pub struct Cli {
     #[arg(short, long)]
     pub event_based: bool,
 
-    /// For use with --continuous. Determines whether the random number generator should be skewed to somewhat match the incorrect discrete probabilities.
+    /// For use with --continuous. Samples stay durations from the exact distribution implied by the incorrect discrete probabilities,
+    /// so that results match the discrete version.
     #[arg(short, long, requires = "continuous")]
     pub skew: bool,
 
+    /// For use with --continuous, --continuous-heap or --event-based. Samples stay durations from an exponential distribution
+    /// with the same mean as the triangular one (half the max stay), so that the lot is an M/M/c queue
+    /// and matches the Erlang formulas in the analytic estimate.
+    #[arg(long, conflicts_with = "skew", requires = "continuous_modes")]
+    pub exponential_stay: bool,
+
     /// Uses a binary search approach to determine the best capacity, instead of just increasing by one constantly.
     #[arg(short, long)]
     pub binary_search: bool,
 
+    /// The number of capacities the binary search simulates at once.
+    /// Defaults to the number of threads that the runs of a single capacity leave idle.
+    /// More probes take fewer rounds on machines with many cores, and with --seed, the result is the same whatever the number.
+    #[arg(long, requires = "binary_search", value_parser = clap::value_parser!(u32).range(1..))]
+    pub probes: Option<u32>,
+
+    /// Seeds the random number generator, so that the results can be reproduced.
+    /// Each run of each capacity gets its own random numbers, whatever order they are simulated in.
+    #[arg(long)]
+    pub seed: Option<u64>,
+
     /// The maximum amount of time a car will stay in the lot, in seconds. Defaults to 8 hours.
     #[arg(short, long, default_value_t = 28800)]
     pub max_stay: u32,
@@ -65,4 +106,425 @@ This only exists as a baseline to compare how much more performant the optimized
         conflicts_with_all(["continuous", "binary_search"])
     )]
     pub faithful: bool,
+
+    /// Adds a customer class that shares the lot with the default traffic,
+    /// in the format `NAME,rate=CARS_PER_HOUR[,stay=MIN/MODE/MAX|depart=MIN/MODE/MAX][,hours=FROM-TO][,priority=N][,permit]`.
+    /// Stays are in seconds, and hours are hours of the day during which the class arrives.
+    /// Instead of a stay, `depart` is the hour of the day at which drivers leave (e.g. `16/17/19`).
+    /// Classes with a higher priority go first with `--queue-discipline priority` (the default traffic has a priority of 0).
+    /// Classes marked with `permit` can use the spots reserved by --permits.
+    /// Can be used multiple times. Requires --continuous, --continuous-heap or --event-based,
+    /// since the discrete method can't give each class its own stays.
+    #[arg(
+        long = "class",
+        value_name = "SPEC",
+        requires = "continuous_modes",
+        conflicts_with = "faithful"
+    )]
+    pub classes: Vec<ClassSpec>,
+
+    /// Adds a special event (e.g. a concert or a shift change), in the format
+    /// `NAME,cars=N,arrive=MIN/MODE/MAX,leave=MIN/MODE/MAX[,days=DAY/DAY/...]`.
+    /// Each time the event happens, about `cars` cars arrive at hours of the day drawn from `arrive`,
+    /// and leave together within 10 minutes of the end of the event, drawn from `leave`,
+    /// on the given days (`mon` to `sun`, every day by default).
+    /// The event's cars are reported as a class of their own. Can be used multiple times. Requires --event-based.
+    #[arg(long = "event", value_name = "SPEC", requires = "event_based")]
+    pub events: Vec<EventSpec>,
+
+    /// The probability that an arriving driver doesn't join the queue, for each car already waiting in it.
+    /// Requires --event-based.
+    #[arg(
+        long,
+        default_value_t = 0.0,
+        value_name = "PROBABILITY",
+        value_parser = parse_probability,
+        requires = "event_based"
+    )]
+    pub balk_per_car: f32,
+
+    /// The probability that an arriving driver doesn't join the queue when the lot is full
+    /// (i.e. when the "LOT FULL" sign is on). Requires --event-based.
+    #[arg(
+        long,
+        default_value_t = 0.0,
+        value_name = "PROBABILITY",
+        value_parser = parse_probability,
+        requires = "event_based"
+    )]
+    pub balk_when_full: f32,
+
+    /// The mean time, in seconds, that a driver is willing to wait in the queue before giving up.
+    /// Patience is exponentially distributed. Drivers wait forever if this is not set. Requires --event-based.
+    #[arg(long, value_name = "SECONDS", requires = "event_based", value_parser = parse_positive::<f32>)]
+    pub patience: Option<f32>,
+
+    /// The number of cars that fit in the entrance lane. By default, the lane is unlimited.
+    /// Cars turned away from a full lane are lost, so this needs --max-lost (or --spot-cost) unless --overflow is spill.
+    /// Requires --event-based.
+    #[arg(long, value_name = "CARS", requires = "event_based", value_parser = parse_positive::<usize>)]
+    pub queue_storage: Option<usize>,
+
+    /// The percentage of the time that the entrance lane should hold the whole queue,
+    /// used to report the storage length that the lane needs.
+    /// With --queue-storage and the default --overflow, the queue can't grow past the lane, so neither can this.
+    #[arg(
+        long,
+        value_name = "PERCENT",
+        default_value_t = 95.0,
+        value_parser = parse_percentile,
+        requires = "report"
+    )]
+    pub storage_percentile: f32,
+
+    /// What happens to cars that arrive when the entrance lane is full.
+    #[arg(long, value_enum, default_value_t = Overflow::TurnAway, requires = "queue_storage")]
+    pub overflow: Overflow,
+
+    /// Drivers who find no free spot circle the block and try again after a random (exponentially distributed)
+    /// number of seconds with this mean, instead of queueing at the entrance.
+    /// Cars still cruising at the end count as left in the queue. Requires --event-based.
+    #[arg(long, value_name = "SECONDS", requires = "event_based", value_parser = parse_positive::<f32>)]
+    pub cruise: Option<f32>,
+
+    /// The number of failed retries after which a cruising driver gives up.
+    /// By default, drivers keep trying until they find a spot. Requires --cruise.
+    #[arg(
+        long,
+        value_name = "RETRIES",
+        requires = "cruise",
+        value_parser = clap::value_parser!(u32).range(1..)
+    )]
+    pub max_retries: Option<u32>,
+
+    /// The average speed of cruising cars in km/h, used to work out the extra distance travelled.
+    #[arg(
+        long,
+        value_name = "KMH",
+        default_value_t = 20.0,
+        requires = "cruise",
+        value_parser = parse_positive::<f32>
+    )]
+    pub cruise_speed: f32,
+
+    /// The order in which queued cars get to park, out of the cars that have a spot available to them.
+    /// Waiting times for each priority level are shown in the report. Requires --event-based.
+    #[arg(long, value_enum, default_value_t = QueueDiscipline::Fifo, requires = "event_based")]
+    pub queue_discipline: QueueDiscipline,
+
+    /// Keeps some spots free for some classes, in the format `SPOTS[,for=CLASS/CLASS/...]`.
+    /// Other cars are only admitted while more than that many spots are free in the pools that they can use.
+    /// Without any classes, the spots are kept for the classes with a priority above 0 (see --class).
+    /// Requires --event-based.
+    #[arg(long, value_name = "SPEC", requires = "event_based")]
+    pub reserve: Option<ReserveSpec>,
+
+    /// Turns on the "LOT FULL" sign once this fraction of the lot is occupied,
+    /// so that no more cars are admitted until the occupancy drops below it again. Requires --event-based.
+    #[arg(long, value_name = "OCCUPANCY", requires = "event_based", value_parser = parse_fraction)]
+    pub full_sign: Option<f32>,
+
+    /// Also searches for the capacity needed without the admission policy (--reserve and --full-sign),
+    /// and compares the two lots.
+    #[arg(long, requires = "admission")]
+    pub compare_admission: bool,
+
+    /// Models the position of each spot, in the format `ROW_LENGTH[,level=SPOTS][,width=METRES][,aisle=METRES][,stairs=METRES]`.
+    /// Spots are laid out in rows of `ROW_LENGTH` spots, with `level` spots on each level (one level by default),
+    /// and the pools are laid out in order. The walk to the entrance at the start of the first row is `width` metres
+    /// per spot along the row (2.5 by default), `aisle` metres per row (8 by default), and `stairs` metres per level
+    /// (30 by default). The report shows a heatmap of the spots and the average walking distance. Requires --event-based.
+    #[arg(long, value_name = "SPEC", requires = "event_based")]
+    pub layout: Option<Layout>,
+
+    /// How drivers pick a spot out of the free spots in a pool.
+    #[arg(long, value_enum, default_value_t = SpotChoice::Nearest, requires = "layout")]
+    pub spot_choice: SpotChoice,
+
+    /// The hours of the day during which the lot is open, in the format `FROM-TO` (e.g. `7-22`).
+    /// Cars only arrive while the lot is open, and cars still queued at closing time are sent away
+    /// (and counted as left in the queue). Requires --event-based.
+    #[arg(long, value_name = "FROM-TO", value_parser = parse_hours, requires = "event_based")]
+    pub open_hours: Option<(u32, u32)>,
+
+    /// What happens to the cars that are still parked when the lot closes.
+    #[arg(long, value_enum, default_value_t = AtClosing::Leave, requires = "open_hours")]
+    pub at_closing: AtClosing,
+
+    /// The time it takes an entrance gate to serve a car, in seconds.
+    /// Either a fixed time, or a triangular distribution in the format `MIN/MODE/MAX`.
+    /// Without this, cars enter one per second and entering takes no time. Requires --event-based.
+    #[arg(long, value_name = "SECONDS", requires = "event_based")]
+    pub entry_service: Option<ServiceTime>,
+
+    /// The number of entrance gates serving the queue.
+    #[arg(
+        long,
+        default_value_t = 1,
+        value_name = "GATES",
+        requires = "entry_service",
+        value_parser = clap::value_parser!(u32).range(1..)
+    )]
+    pub entry_gates: u32,
+
+    /// Searches for the smallest number of entrance gates that keeps the queue under the threshold
+    /// (assuming there are always enough spots), and then uses that many gates for the capacity search.
+    #[arg(long, requires = "entry_service", conflicts_with = "entry_gates")]
+    pub search_gates: bool,
+
+    /// The time it takes an exit gate to serve a car (e.g. to pay), in seconds.
+    /// Either a fixed time, or a triangular distribution in the format `MIN/MODE/MAX`.
+    /// Departing cars keep their spot until they make it through an exit gate.
+    /// Without this, cars free their spot as soon as they depart. Requires --event-based.
+    /// Conflicts with --faithful, which keeps the assignment's outgoing queue that frees spots right away.
+    #[arg(
+        long,
+        value_name = "SECONDS",
+        requires = "event_based",
+        conflicts_with = "faithful"
+    )]
+    pub exit_service: Option<ServiceTime>,
+
+    /// The number of exit gates serving departing cars.
+    #[arg(
+        long,
+        default_value_t = 1,
+        value_name = "GATES",
+        requires = "exit_service",
+        value_parser = clap::value_parser!(u32).range(1..)
+    )]
+    pub exit_gates: u32,
+
+    /// Adds a pool of typed spots (e.g. compact or accessible) to the lot,
+    /// in the format `NAME[,share=FRACTION|size=SPOTS][,power=KW]`.
+    /// A pool with a power has an EV charger of that power at each spot.
+    /// Exactly one pool must have neither a share nor a size; it gets the rest of the capacity.
+    /// By default, the lot is a single pool named `regular`. Can be used multiple times.
+    /// Without --event-based, there are no vehicle types, so cars only use the pool that gets the rest of the capacity.
+    #[arg(
+        long = "pool",
+        value_name = "SPEC",
+        conflicts_with_all = ["continuous_heap", "faithful"]
+    )]
+    pub pools: Vec<PoolSpec>,
+
+    /// Adds a lot to a network of lots (e.g. a main garage and an overflow surface lot),
+    /// in the format `NAME[,share=FRACTION|size=SPOTS][,distance=METRES][,price=MULTIPLIER]`.
+    /// Drivers try the lots in the order given by --routing, overflowing to the next one when a lot is full.
+    /// Exactly one lot must have neither a share nor a size; its capacity is searched for.
+    /// The price multiplies the tariff. Can be used multiple times.
+    #[arg(
+        long = "lot",
+        value_name = "SPEC",
+        conflicts_with_all = ["pools", "continuous_heap", "faithful"]
+    )]
+    pub lots: Vec<PoolSpec>,
+
+    /// How drivers choose between the lots of a network.
+    #[arg(long, value_enum, default_value_t = Routing::Preference, requires = "lots")]
+    pub routing: Routing,
+
+    /// Adds a type of vehicle, in the format `NAME,share=FRACTION[,pools=POOL/POOL/...][,size=SPOTS]`.
+    /// The vehicle can only park in the listed pools, which it tries in order.
+    /// By default, vehicles can only use the pool that gets the rest of the capacity.
+    /// The size is the number of spots that the vehicle takes up (1 by default), e.g. 0.25 for a motorcycle
+    /// or 3 for a bus; the occupancy of each pool is measured in twelfths of a spot.
+    /// Sizes are an approximation of the total space in each pool:
+    /// a vehicle parks whenever its pool has enough space left, even if that space is spread over several spots.
+    /// EVs also take `battery=KWH[,soc=MIN/MODE/MAX][,target=PERCENT][,idle=MIN/MODE/MAX][,after=leave|move]`;
+    /// at a charger, they stay until they reach the target charge (plus any idle time),
+    /// then either leave or move to one of their pools without chargers.
+    /// Can be used multiple times. Requires --event-based.
+    #[arg(long = "vehicle", value_name = "SPEC", requires = "event_based")]
+    pub vehicles: Vec<VehicleSpec>,
+
+    /// Searches for the size of each pool separately, instead of keeping their shares of the capacity fixed.
+    #[arg(long, requires = "pools")]
+    pub size_pools: bool,
+
+    /// The number of spots reserved for permit holders, on top of the walk-in capacity being searched for.
+    /// Permit holders are the classes marked with `permit` in --class;
+    /// they park in a reserved spot if one is free, and queue for a walk-in spot otherwise.
+    /// Requires --event-based.
+    #[arg(long, value_name = "SPOTS", requires = "event_based", value_parser = parse_positive::<usize>)]
+    pub permits: Option<usize>,
+
+    /// Releases any free reserved spots to walk-ins for the rest of the day,
+    /// once this many seconds have passed since permit holders start arriving
+    /// (the start of their `hours`, or midnight).
+    /// By default, walk-ins can never use reserved spots.
+    #[arg(long, value_name = "SECONDS", requires = "permits")]
+    pub release_after: Option<u32>,
+
+    /// The probability that a permit holder shows up on a given day.
+    /// With this set, permit holders arrive individually (once a day at most) during the hours of the permit classes,
+    /// instead of at the rates of the permit classes.
+    #[arg(long, value_name = "PROBABILITY", requires = "permits", value_parser = parse_probability)]
+    pub show_up: Option<f32>,
+
+    /// The number of permits sold, which may be more than the number of reserved spots.
+    /// Defaults to the number of reserved spots.
+    #[arg(
+        long,
+        value_name = "PERMITS",
+        requires = "show_up",
+        conflicts_with = "oversell"
+    )]
+    pub permits_sold: Option<usize>,
+
+    /// Finds the largest number of permits that can be sold for the reserved spots,
+    /// while keeping the fraction of permit holders finding no reserved spot at or under this target.
+    /// The walk-in spots are then searched for with that many permits sold.
+    /// At most 100 permits are sold per reserved spot.
+    #[arg(long, value_name = "FRACTION", requires = "show_up", value_parser = parse_probability)]
+    pub oversell: Option<f32>,
+
+    /// The maximum average number of drivers per run that are allowed to give up on parking
+    /// (by balking, reneging, being turned away, or being priced out) in order for a capacity to be considered acceptable.
+    /// By default, only --threshold is used. Requires --event-based.
+    #[arg(long, value_name = "CARS", requires = "event_based")]
+    pub max_lost: Option<f32>,
+
+    /// The cost of each spot over the simulated duration (e.g. to build and maintain it).
+    /// With this set, the capacity with the lowest expected total cost is searched for instead of using --threshold,
+    /// where the total cost includes --wait-cost and --lost-cost.
+    /// Every capacity is tried in turn, so this can't be used with --binary-search. Requires --event-based.
+    #[arg(
+        long,
+        value_name = "COST",
+        requires = "event_based",
+        conflicts_with = "binary_search",
+        value_parser = parse_positive::<f64>
+    )]
+    pub spot_cost: Option<f64>,
+
+    /// The cost of each second that a car spends waiting in the queue. Requires --spot-cost.
+    #[arg(
+        long,
+        value_name = "COST",
+        default_value_t = 0.0,
+        requires = "spot_cost",
+        value_parser = parse_non_negative::<f64>
+    )]
+    pub wait_cost: f64,
+
+    /// The cost of each customer that gives up on parking or is turned away. Requires --spot-cost.
+    #[arg(
+        long,
+        value_name = "COST",
+        default_value_t = 0.0,
+        requires = "spot_cost",
+        value_parser = parse_non_negative::<f64>
+    )]
+    pub lost_cost: f64,
+
+    /// The tariff charged for parking, in the format `RATE[,cap=PRICE][,free=SECONDS][,per=SECONDS]`.
+    /// The rate is the price per hour, the cap is the most charged for any 24 hours,
+    /// the free period isn't charged, and stays are billed in increments of `per` seconds (an hour by default).
+    /// Revenue is shown in the report. Requires --event-based.
+    #[arg(long, value_name = "SPEC", requires = "event_based")]
+    pub tariff: Option<Tariff>,
+
+    /// Changes the price with the occupancy of the lot, in the format `OCCUPANCY=MULTIPLIER[,OCCUPANCY=MULTIPLIER...]`.
+    /// The tariff is multiplied by the multiplier of the highest occupancy (as a fraction) that has been reached,
+    /// e.g. `0.9=1.5` charges 1.5 times the normal price once the lot is over 90% full.
+    /// Drivers pay the price at the time that they park. Requires --tariff.
+    #[arg(long, value_name = "SPEC", requires = "tariff")]
+    pub surge: Option<SurgePricing>,
+
+    /// The price elasticity of demand. An arriving driver who sees the price multiplied by `m`
+    /// still comes in with a probability of `m^-ELASTICITY`. Requires --surge.
+    #[arg(
+        long,
+        value_name = "ELASTICITY",
+        default_value_t = 0.0,
+        requires = "surge",
+        value_parser = parse_non_negative::<f32>
+    )]
+    pub price_elasticity: f32,
+
+    /// The price elasticity of the stay. Drivers who pay `m` times the normal price
+    /// stay `m^-ELASTICITY` times as long. Requires --surge.
+    #[arg(
+        long,
+        value_name = "ELASTICITY",
+        default_value_t = 0.0,
+        requires = "surge",
+        value_parser = parse_non_negative::<f32>
+    )]
+    pub stay_elasticity: f32,
+
+    /// Also searches for the capacity needed with a static price, and compares the two lots. Requires --surge.
+    #[arg(long, requires = "surge")]
+    pub compare_static: bool,
+
+    /// Starts each run with some of the lot occupied, either as a fraction of the capacity, or `steady`
+    /// to draw the number of parked cars from the steady state of the demand (cars that don't fit start in the queue).
+    /// The remaining stays of the parked cars are drawn from the equilibrium distribution of the stays.
+    /// Requires --event-based.
+    #[arg(long, value_name = "FRACTION|steady", requires = "event_based")]
+    pub initial_occupancy: Option<InitialOccupancy>,
+
+    /// Discards the statistics from this many seconds at the start of each run, or `auto` to detect the warm-up period
+    /// using Welch's method (only meaningful if the demand doesn't change with the time of day).
+    /// Time-based statistics (such as waits and spot-hours) only count the time after the warm-up period.
+    /// Requires --event-based.
+    #[arg(long, value_name = "SECONDS|auto", requires = "event_based")]
+    pub warm_up: Option<WarmUp>,
+
+    /// Closes some spots on a schedule, in the format `SPOTS[,hours=FROM-TO][,days=DAY/DAY/...][,pool=NAME]`.
+    /// The spots are closed between the hours of the day (all day by default) on the days of the week
+    /// (`mon` to `sun`, every day by default), where the simulation starts on a Monday at midnight.
+    /// Without a pool, the spots are closed in the pool that is sized by the search.
+    /// Cars already parked in closed spots stay until they leave. Can be given multiple times.
+    #[arg(long = "closure", value_name = "SPEC", conflicts_with = "faithful")]
+    pub closures: Vec<ClosureSpec>,
+
+    /// Prints a per-class breakdown of the results for the final capacity. Requires --event-based.
+    #[arg(long, requires = "event_based")]
+    pub report: bool,
+}
+
+/// Parses a probability, which must be between 0 and 1.
+fn parse_probability(value: &str) -> Result<f32, String> {
+    match value.parse::<f32>() {
+        Ok(probability) if (0.0..=1.0).contains(&probability) => Ok(probability),
+        _ => Err(format!("`{value}` must be a probability between 0 and 1")),
+    }
+}
+
+/// Parses a fraction of a whole, which must be above 0 and at most 1.
+fn parse_fraction(value: &str) -> Result<f32, String> {
+    match value.parse::<f32>() {
+        Ok(fraction) if fraction > 0.0 && fraction <= 1.0 => Ok(fraction),
+        _ => Err(format!(
+            "`{value}` must be a fraction above 0 and at most 1"
+        )),
+    }
+}
+
+/// Parses a percentile, which must be above 0 and at most 100.
+fn parse_percentile(value: &str) -> Result<f32, String> {
+    match value.parse::<f32>() {
+        Ok(percentile) if percentile > 0.0 && percentile <= 100.0 => Ok(percentile),
+        _ => Err(format!(
+            "`{value}` must be a percentage above 0 and at most 100"
+        )),
+    }
+}
+
+/// Parses a number that must be above 0.
+fn parse_positive<T: FromStr + PartialOrd + Default>(value: &str) -> Result<T, String> {
+    match value.parse::<T>() {
+        Ok(number) if number > T::default() => Ok(number),
+        _ => Err(format!("`{value}` must be a number above 0")),
+    }
+}
+
+/// Parses a number that must be 0 or above.
+fn parse_non_negative<T: FromStr + PartialOrd + Default>(value: &str) -> Result<T, String> {
+    match value.parse::<T>() {
+        Ok(number) if number >= T::default() => Ok(number),
+        _ => Err(format!("`{value}` must be a number that is 0 or above")),
+    }
 }
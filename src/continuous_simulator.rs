@@ -1,44 +1,51 @@
-use std::{cmp::Reverse, collections::BinaryHeap};
+use std::{cmp::Reverse, collections::BinaryHeap, collections::VecDeque};
 
 use crate::{
-    random_generator, simulator::Simulator, triangular_distribution::TriangularPdfSampler,
+    customer_class::CustomerClass, random_generator, schedule::Schedule, simulator::Simulator,
 };
 
 pub struct ContinuousHeapSimulator {
     occupancy: usize,
+    /// The number of open spots.
     capacity: usize,
+    /// The number of spots, whether they are open or closed.
+    size: usize,
+    closed: usize,
+    schedule: Schedule,
     clock: u32,
     steps: u32,
     departure_times: BinaryHeap<Reverse<u32>>,
-    incoming: usize,
-    pdf: TriangularPdfSampler,
-    cars_per_second: f32,
+    /// The class of each car in the queue.
+    incoming: VecDeque<usize>,
+    classes: Vec<CustomerClass>,
 }
 
 impl ContinuousHeapSimulator {
-    pub fn new(capacity: usize, max_time: u32, steps: u32, cars_per_hour: f32, skew: bool) -> Self {
+    pub fn new(capacity: usize, steps: u32, classes: &[CustomerClass], schedule: Schedule) -> Self {
         Self {
             occupancy: 0,
             capacity,
+            size: capacity,
+            closed: 0,
+            schedule,
             clock: 0,
             steps,
             departure_times: BinaryHeap::new(),
-            incoming: 0,
-            pdf: TriangularPdfSampler::new(0.0, (max_time / 2) as f32, max_time as f32, skew),
-            cars_per_second: cars_per_hour / 3600.0,
+            incoming: VecDeque::new(),
+            classes: classes.to_vec(),
         }
     }
 
     fn can_park(&self) -> bool {
-        self.occupancy != self.capacity
+        self.occupancy < self.capacity
     }
 
-    /// Generates a departure time for a car, add it to the [`Self::departure_times`] list.
+    /// Generates a departure time for a car of a given class, add it to the [`Self::departure_times`] list.
     /// Does **not** change [`Self::incoming`].
     /// This function assumes that the caller has already checked that the lot is not full.
-    fn park_car<R: rand::Rng>(&mut self, rng: &mut R) {
+    fn park_car<R: rand::Rng>(&mut self, rng: &mut R, class: usize) {
         // Generate a departure time and add it to the heap
-        let departure_time = self.clock + self.pdf.sample(rng);
+        let departure_time = self.clock + self.classes[class].sample_stay(rng, self.clock);
         self.occupancy += 1;
         self.departure_times.push(Reverse(departure_time));
     }
@@ -59,19 +66,29 @@ impl ContinuousHeapSimulator {
 impl Simulator for ContinuousHeapSimulator {
     fn simulate<T: rand::Rng>(&mut self, rng: &mut T) {
         while self.clock < self.steps {
-            // Determine whether car likely arrives
-            let car_arrived = random_generator::event_occurred(rng, self.cars_per_second);
-            if car_arrived {
-                self.incoming += 1;
+            // Determine whether a car of each class likely arrives
+            for (index, class) in self.classes.iter().enumerate() {
+                if class.arrives_at(self.clock)
+                    && random_generator::event_occurred(rng, class.cars_per_second)
+                {
+                    self.incoming.push_back(index);
+                }
             }
 
             // Handle any departures for this timestamp
             self.handle_departures(self.clock);
 
+            // Close or reopen any scheduled spots, without evicting any cars
+            while let Some(change) = self.schedule.due(self.clock) {
+                self.closed = self.closed.saturating_add_signed(change.closed);
+                self.capacity = self.size.saturating_sub(self.closed);
+            }
+
             // Try to park car in queue
-            if self.incoming != 0 && self.can_park() {
-                self.park_car(rng);
-                self.incoming -= 1;
+            if self.can_park()
+                && let Some(class) = self.incoming.pop_front()
+            {
+                self.park_car(rng, class);
             }
 
             self.clock += 1;
@@ -79,6 +96,6 @@ impl Simulator for ContinuousHeapSimulator {
     }
 
     fn cars_left(&self) -> usize {
-        self.incoming
+        self.incoming.len()
     }
 }
@@ -0,0 +1,215 @@
+use std::str::FromStr;
+
+use crate::triangular_distribution::{StaySampler, TriangularPdfSampler};
+
+/// The number of seconds in a day, used for time-of-day calculations.
+pub const DAY: u32 = 86400;
+
+/// A group of customers that share the lot, with its own arrival rate and stay distribution.
+#[derive(Clone)]
+pub struct CustomerClass {
+    pub name: String,
+    pub cars_per_second: f32,
+    /// If set, cars only arrive between these seconds of the day.
+    /// The window may wrap around midnight (e.g. 18:00 to 07:00).
+    pub hours: Option<(u32, u32)>,
+    pub stay: StaySampler,
+    /// If set, drivers leave at a time of day drawn from this distribution (in seconds of the day)
+    /// instead of staying for a duration drawn from [`Self::stay`].
+    pub departure: Option<TriangularPdfSampler>,
+    /// Whether the drivers of this class hold permits for the reserved spots.
+    pub permit: bool,
+    /// The priority of the class in the queue, if it is served by priority.
+    pub priority: u32,
+}
+
+impl CustomerClass {
+    /// Whether a car of this class may arrive at a given simulation time.
+    pub fn arrives_at(&self, timestamp: u32) -> bool {
+        self.hours
+            .is_none_or(|hours| within_hours(hours, timestamp))
+    }
+
+    /// Generates the stay of a car that parks at a given simulation time.
+    /// Drivers that leave at a time of day that has already passed leave on the next day.
+    pub fn sample_stay<R: rand::Rng>(&self, rng: &mut R, timestamp: u32) -> u32 {
+        let Some(departure) = &self.departure else {
+            return self.stay.sample(rng);
+        };
+        let leave = departure.sample(rng) % DAY;
+        (leave + DAY - timestamp % DAY - 1) % DAY + 1
+    }
+}
+
+/// Whether a simulation time is between two seconds of the day.
+/// The window may wrap around midnight (e.g. 18:00 to 07:00).
+pub fn within_hours((from, to): (u32, u32), timestamp: u32) -> bool {
+    let time_of_day = timestamp % DAY;
+    if from <= to {
+        (from..to).contains(&time_of_day)
+    } else {
+        time_of_day >= from || time_of_day < to
+    }
+}
+
+/// A customer class as specified on the command line.
+///
+/// The format is `NAME,rate=CARS_PER_HOUR[,stay=MIN/MODE/MAX|depart=MIN/MODE/MAX][,hours=FROM-TO][,priority=N][,permit]`,
+/// where the stay is given in seconds and the hours are given as hours of the day.
+/// If no stay is given, the class uses the same distribution as the default traffic.
+/// Instead of a stay, `depart` gives the hour of the day at which drivers leave (e.g. `16/17/19`).
+/// Drivers of a class with `permit` can use the reserved spots.
+/// Classes with a higher priority go first in a priority queue (the default traffic has a priority of 0).
+#[derive(Clone)]
+pub struct ClassSpec {
+    pub name: String,
+    pub cars_per_hour: f32,
+    pub stay: Option<TriangularPdfSampler>,
+    pub departure: Option<TriangularPdfSampler>,
+    pub hours: Option<(u32, u32)>,
+    pub priority: u32,
+    pub permit: bool,
+}
+
+impl ClassSpec {
+    /// Creates the class described by this spec.
+    /// `default_stay` is used if the spec does not have its own stay distribution.
+    pub fn build(&self, default_stay: &StaySampler) -> CustomerClass {
+        CustomerClass {
+            name: self.name.clone(),
+            cars_per_second: self.cars_per_hour / 3600.0,
+            hours: self.hours,
+            departure: self.departure,
+            permit: self.permit,
+            priority: self.priority,
+            stay: self
+                .stay
+                .map_or_else(|| default_stay.clone(), StaySampler::Triangular),
+        }
+    }
+}
+
+/// Parses a triangular distribution in the format `MIN/MODE/MAX`.
+pub fn parse_triangular(value: &str) -> Result<TriangularPdfSampler, String> {
+    let parts = value
+        .split('/')
+        .map(|part| {
+            part.parse::<u32>()
+                .map_err(|e| format!("invalid duration `{part}`: {e}"))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    let &[min, mode, max] = parts.as_slice() else {
+        return Err(format!("`{value}` must be in the format MIN/MODE/MAX"));
+    };
+    if !(min <= mode && mode <= max && min < max) {
+        return Err(format!(
+            "`{value}` must satisfy MIN <= MODE <= MAX and MIN < MAX"
+        ));
+    }
+    Ok(TriangularPdfSampler::new(
+        min as f32,
+        mode as f32,
+        max as f32,
+    ))
+}
+
+/// Parses an hour of the day, returning the second of the day.
+fn parse_hour(hour: &str) -> Result<u32, String> {
+    match hour.parse::<f32>() {
+        Ok(hour) if (0.0..=24.0).contains(&hour) => Ok((hour * 3600.0) as u32),
+        _ => Err(format!("invalid hour `{hour}`")),
+    }
+}
+
+pub fn parse_hours(value: &str) -> Result<(u32, u32), String> {
+    let (from, to) = value
+        .split_once('-')
+        .ok_or_else(|| format!("hours `{value}` must be in the format FROM-TO"))?;
+    let (from, to) = (parse_hour(from)?, parse_hour(to)?);
+    // The whole day is `0-24`, so hours that start and end at the same time of day would be empty
+    if from % DAY == to % DAY && from >= to {
+        return Err(format!(
+            "hours `{value}` must not start and end at the same time (use 0-24 for the whole day)"
+        ));
+    }
+    Ok((from, to))
+}
+
+/// Parses a triangular distribution of the time of day in the format `MIN/MODE/MAX`,
+/// where each part is an hour of the day.
+pub fn parse_departure(value: &str) -> Result<TriangularPdfSampler, String> {
+    let hours = value
+        .split('/')
+        .map(parse_hour)
+        .collect::<Result<Vec<_>, _>>()?;
+    let &[min, mode, max] = hours.as_slice() else {
+        return Err(format!("`{value}` must be in the format MIN/MODE/MAX"));
+    };
+    if !(min <= mode && mode <= max && min < max) {
+        return Err(format!(
+            "`{value}` must satisfy MIN <= MODE <= MAX and MIN < MAX"
+        ));
+    }
+    Ok(TriangularPdfSampler::new(
+        min as f32,
+        mode as f32,
+        max as f32,
+    ))
+}
+
+impl FromStr for ClassSpec {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parts = s.split(',');
+        let name = parts.next().unwrap_or_default();
+        if name.is_empty() || name.contains('=') {
+            return Err("the class must start with a name".into());
+        }
+
+        let mut spec = Self {
+            name: name.into(),
+            cars_per_hour: 0.0,
+            stay: None,
+            departure: None,
+            hours: None,
+            priority: 0,
+            permit: false,
+        };
+
+        for part in parts {
+            if part == "permit" {
+                spec.permit = true;
+                continue;
+            }
+            let (key, value) = part
+                .split_once('=')
+                .ok_or_else(|| format!("expected KEY=VALUE, found `{part}`"))?;
+            match key {
+                "rate" => {
+                    spec.cars_per_hour = value
+                        .parse()
+                        .map_err(|e| format!("invalid rate `{value}`: {e}"))?;
+                }
+                "stay" => spec.stay = Some(parse_triangular(value)?),
+                "depart" => spec.departure = Some(parse_departure(value)?),
+                "hours" => spec.hours = Some(parse_hours(value)?),
+                "priority" => {
+                    spec.priority = value
+                        .parse()
+                        .map_err(|e| format!("invalid priority `{value}`: {e}"))?;
+                }
+                _ => return Err(format!("unknown class option `{key}`")),
+            }
+        }
+
+        if spec.stay.is_some() && spec.departure.is_some() {
+            return Err("the class can't have both a stay and a departure time".into());
+        }
+        if spec.cars_per_hour <= 0.0 || spec.cars_per_hour >= 3600.0 {
+            return Err("the class needs a rate between 0 and 3600 cars per hour".into());
+        }
+
+        Ok(spec)
+    }
+}
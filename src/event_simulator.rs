@@ -1,98 +1,1052 @@
-use std::{cmp::Reverse, collections::BinaryHeap};
+use std::{cmp::Reverse, collections::BinaryHeap, collections::VecDeque};
 
-use crate::{simulator::Simulator, triangular_distribution::TriangularPdfSampler};
+use rand::seq::{IndexedRandom, IteratorRandom};
 
-pub struct EventSimulator {
-    occupancy: usize,
-    capacity: usize,
+use crate::{
+    charging::AfterCharging,
+    customer_class::DAY,
+    gate::Gates,
+    opening_hours::AtClosing,
+    parking_lot::{PooledLot, UNITS_PER_SPOT},
+    queue::{EntranceQueue, Overflow, QueueDiscipline, QueuedCar},
+    random_generator,
+    report::RunStats,
+    scenario::Scenario,
+    schedule::Schedule,
+    simulator::Simulator,
+    special_event::EventSpec,
+    vehicle,
+    warm_up::InitialOccupancy,
+};
+
+/// A car that will arrive at the lot.
+struct Arrival {
+    time: u32,
+    class: usize,
+}
+
+/// A parked car's departure time and its index in [`EventSimulator::cars`].
+/// These are packed into a single integer (ordered by time first)
+/// so that the departure heap is as fast as a heap of plain timestamps.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+struct Departure(u64);
+
+impl Departure {
+    #[inline]
+    fn new(time: u32, car: usize) -> Self {
+        assert!(u32::try_from(car).is_ok(), "too many cars parked at once");
+        Self((u64::from(time) << 32) | car as u64)
+    }
+
+    #[inline]
+    fn time(self) -> u32 {
+        (self.0 >> 32) as u32
+    }
+
+    #[inline]
+    fn car(self) -> usize {
+        self.0 as u32 as usize
+    }
+}
+
+/// A car taking up a spot in the lot.
+#[derive(Clone, Copy)]
+struct ParkedCar {
+    class: usize,
+    vehicle: usize,
+    /// The spot that the car is parked in (see [`PooledLot::park`]).
+    spot: usize,
+    /// The time at which the car parked.
+    since: u32,
+    /// The price that the driver pays per unit of the tariff, fixed at the time that they park.
+    price: f32,
+}
+
+impl ParkedCar {
+    /// What the driver pays for the time between parking and freeing their spot, if the lot has a tariff.
+    fn fare(&self, scenario: &Scenario, until: u32) -> f64 {
+        scenario.tariff.map_or(0.0, |tariff| {
+            f64::from(tariff.charge(until - self.since) * self.price)
+        })
+    }
+}
+
+/// A driver circling the block after finding the lot full.
+/// Ordered by the time that they try again first.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+struct CruisingCar {
+    /// The time at which the driver tries the lot again.
+    retry_time: u32,
+    /// The time at which the driver started their current lap.
+    since: u32,
+    /// The number of times that the driver has tried again.
+    retries: u32,
+    class: usize,
+    vehicle: usize,
+    /// See [`QueuedCar::leave_by`].
+    leave_by: u32,
+}
+
+pub struct EventSimulator<'a> {
+    scenario: &'a Scenario,
+    lot: PooledLot,
     clock: u32,
     steps: u32,
-    arrival_times: Vec<u32>,
+    arrivals: Vec<Arrival>,
     arrival_index: usize,
-    departure_times: BinaryHeap<Reverse<u32>>,
-    incoming: usize,
-    pdf: TriangularPdfSampler,
-    cars_per_second: f32,
+    /// The time at which each day's special event ends, for each class (empty for classes that aren't events).
+    event_ends: Vec<Vec<u32>>,
+    departure_times: BinaryHeap<Reverse<Departure>>,
+    /// The cars taking up spots, indexed by their departures.
+    /// The indices of cars that have left are reused, and kept in [`Self::free_cars`].
+    cars: Vec<Option<ParkedCar>>,
+    free_cars: Vec<usize>,
+    incoming: EntranceQueue,
+    /// The entrance gates, if they are modelled.
+    /// Without gates, one car can enter per tick and entering takes no time.
+    /// Cars being served are stored alongside the spot that they will park in.
+    entry_gates: Option<Gates<(QueuedCar, usize)>>,
+    /// EVs that will move off their charger to a regular spot, as the time that they unplug,
+    /// their departure time and their index in [`Self::cars`].
+    moves: BinaryHeap<Reverse<(u32, u32, usize)>>,
+    /// The departures of cars waiting for an exit gate.
+    /// These cars still occupy their spots.
+    outgoing: VecDeque<Departure>,
+    /// The exit gates, if they are modelled, for the departures of cars being served.
+    /// Without gates, cars free their spots as soon as they depart.
+    exit_gates: Option<Gates<Departure>>,
+    /// The scheduled closures and reopenings of spots.
+    schedule: Schedule,
+    /// The next time that the lot closes, if it has opening hours.
+    next_closing: Option<u32>,
+    /// Drivers circling the block, in the order that they try the lot again.
+    cruising: BinaryHeap<Reverse<CruisingCar>>,
+    /// Whether the statistics from the warm-up period have been discarded yet.
+    warmed_up: bool,
+    /// The interval between samples of the number of occupied spots, and the samples,
+    /// if the occupancy is being traced.
+    trace: Option<(u32, Vec<u32>)>,
+    stats: RunStats,
 }
 
-impl EventSimulator {
-    pub fn new<R: rand::Rng>(
-        capacity: usize,
-        max_time: u32,
-        steps: u32,
-        cars_per_hour: f32,
-        skew: bool,
-        rng: &mut R,
-    ) -> Self {
+impl<'a> EventSimulator<'a> {
+    pub fn new<R: rand::Rng>(capacity: usize, scenario: &'a Scenario, rng: &mut R) -> Self {
         let mut sim = Self {
-            occupancy: 0,
-            capacity,
-            steps,
+            scenario,
+            lot: scenario.lot(capacity),
+            steps: scenario.steps,
             clock: 0,
-            arrival_times: Vec::new(),
+            arrivals: Vec::new(),
             arrival_index: 0,
+            event_ends: vec![Vec::new(); scenario.classes.len()],
             departure_times: BinaryHeap::new(),
-            incoming: 0,
-            pdf: TriangularPdfSampler::new(0.0, (max_time / 2) as f32, max_time as f32, skew),
-            cars_per_second: cars_per_hour / 3600.0,
+            cars: Vec::new(),
+            free_cars: Vec::new(),
+            incoming: EntranceQueue::default(),
+            entry_gates: scenario.entry_gates.map(|config| Gates::new(config.count)),
+            moves: BinaryHeap::new(),
+            outgoing: VecDeque::new(),
+            exit_gates: scenario.exit_gates.map(|config| Gates::new(config.count)),
+            schedule: Schedule::new(scenario.capacity_changes.clone()),
+            next_closing: scenario
+                .opening_hours
+                .and_then(|hours| hours.next_closing(0)),
+            cruising: BinaryHeap::new(),
+            warmed_up: scenario.warm_up == 0,
+            trace: None,
+            stats: RunStats::new(scenario.classes.len(), scenario.pools.len()),
         };
 
         sim.precompute_arrivals(rng);
+        if let Some(initial) = scenario.initial_occupancy {
+            sim.fill_lot(rng, capacity, initial);
+        }
 
         sim
     }
 
     /// Precomputes arrival times using a geometric distribution to generate time between events,
     /// where the number of cars per second is the chance of the event happening.
-    /// This function guarantees that two cars will not arrive in the same second,
-    /// i.e. there will never be a need to add a check for whether there are multiple arrivals
-    /// for a single timestamp.
+    /// This function guarantees that two cars of the same class will not arrive in the same second,
+    /// but cars of different classes may.
     fn precompute_arrivals<R: rand::Rng>(&mut self, rng: &mut R) {
-        let mut clock = 0;
-        let ln_1_p = (1.0 - self.cars_per_second).ln();
-        while clock < self.steps {
-            // Calculate the time until the next car arrival
-            let x = rng.random::<f32>();
-            let delta_time = ((x.ln() / ln_1_p) as i64).max(0) as u32;
-            // Increment clock by that time
-            clock = clock.saturating_add(delta_time);
-            if clock >= self.steps {
-                break;
+        let per_holder = self
+            .scenario
+            .permits
+            .is_some_and(|permits| permits.holders.is_some());
+
+        for (class_index, class) in self.scenario.classes.iter().enumerate() {
+            // Special events generate their own arrivals
+            if (per_holder && class.permit) || class.cars_per_second == 0.0 {
+                continue;
+            }
+            let mut clock = 0;
+            let ln_1_p = (1.0 - class.cars_per_second).ln();
+            while clock < self.steps {
+                // Calculate the time until the next car arrival
+                let x = rng.random::<f32>();
+                let delta_time = ((x.ln() / ln_1_p) as i64).max(0) as u32;
+                // Increment clock by that time
+                clock = clock.saturating_add(delta_time);
+                if clock >= self.steps {
+                    break;
+                }
+                if class.arrives_at(clock) {
+                    self.arrivals.push(Arrival {
+                        time: clock,
+                        class: class_index,
+                    });
+                }
+                // Advance by one additional second so that no two cars arrive at the same time
+                clock = clock.saturating_add(1);
+            }
+        }
+
+        if per_holder {
+            self.precompute_permit_arrivals(rng);
+        }
+        for (class, event) in &self.scenario.events {
+            let (arrivals, ends) = event.occurrences(rng, self.steps);
+            self.event_ends[*class] = ends;
+            self.arrivals
+                .extend(arrivals.into_iter().map(|time| Arrival {
+                    time,
+                    class: *class,
+                }));
+        }
+
+        // Nobody arrives while the lot is closed
+        if let Some(hours) = self.scenario.opening_hours {
+            self.arrivals.retain(|arrival| hours.is_open(arrival.time));
+        }
+
+        // Merge the arrivals of every class
+        if self.scenario.classes.len() > 1 || per_holder || !self.scenario.events.is_empty() {
+            self.arrivals.sort_by_key(|arrival| arrival.time);
+        }
+    }
+
+    /// Parks cars in the lot before the simulation starts.
+    /// Their remaining stays are drawn from the equilibrium distribution of the stays,
+    /// i.e. a stay is picked with a probability proportional to its length
+    /// (longer stays are more likely to be in progress), and the car is some uniformly random way through it.
+    fn fill_lot<R: rand::Rng>(&mut self, rng: &mut R, capacity: usize, initial: InitialOccupancy) {
+        let scenario = self.scenario;
+        // Cars going to special events only arrive for them
+        let classes = scenario
+            .classes
+            .iter()
+            .enumerate()
+            .filter(|(_, class)| class.cars_per_second > 0.0)
+            .collect::<Vec<_>>();
+        if classes.is_empty() {
+            return;
+        }
+        let count = match initial {
+            // A lot with unlimited capacity can't be some fraction full
+            InitialOccupancy::Fraction(_) if capacity == usize::MAX => return,
+            InitialOccupancy::Fraction(fraction) => {
+                let spots = scenario.pool_sizes(capacity).iter().sum::<usize>();
+                (f64::from(fraction) * spots as f64).round() as usize
+            }
+            // The number of cars in a lot with unlimited capacity follows a Poisson distribution
+            InitialOccupancy::Steady => random_generator::poisson(
+                rng,
+                classes
+                    .iter()
+                    .map(|(_, class)| f64::from(class.cars_per_second * class.stay.mean()))
+                    .sum(),
+            ),
+        };
+        let max_stay = classes
+            .iter()
+            .map(|(_, class)| class.stay.max())
+            .max()
+            .unwrap_or_default();
+        // Cars that stay for no time at all are never in the lot
+        if max_stay == 0 {
+            return;
+        }
+
+        for _ in 0..count {
+            // Pick a class by its share of the arrivals, and a stay by its length
+            let (class, stay) = loop {
+                let &(class, customer_class) = classes
+                    .choose_weighted(rng, |(_, class)| class.cars_per_second)
+                    .unwrap();
+                let stay = customer_class.stay.sample(rng);
+                if rng.random::<f32>() * (max_stay as f32) < stay as f32 {
+                    break (class, stay);
+                }
+            };
+            let remaining = rng.random_range(1..=stay);
+            let vehicle = vehicle::sample(rng, &scenario.vehicles);
+
+            if let Some(pool) = self.find_spot(class, vehicle) {
+                let spot = self
+                    .lot
+                    .park(rng, pool, scenario.vehicles[vehicle].units, 0);
+                // These cars aren't counted as having parked, so they don't pay either
+                let car = self.add_car(ParkedCar {
+                    class,
+                    vehicle,
+                    spot,
+                    since: 0,
+                    price: 0.0,
+                });
+                self.departure_times
+                    .push(Reverse(Departure::new(remaining, car)));
+                let spot_time = self.spot_time(vehicle, 0, remaining);
+                self.stats.classes[class].spot_time += spot_time;
+                self.stats.pools[pool].spot_time += spot_time;
+            } else {
+                self.incoming.push(QueuedCar {
+                    arrival_time: 0,
+                    class,
+                    vehicle,
+                    deadline: EntranceQueue::deadline(rng, 0, scenario.patience),
+                    leave_by: u32::MAX,
+                });
+            }
+        }
+    }
+
+    /// Precomputes the arrivals of individual permit holders.
+    /// Every day, each holder shows up with a fixed probability, at a uniformly random time
+    /// during the hours of a permit class (picked using the arrival rates of the classes as weights).
+    fn precompute_permit_arrivals<R: rand::Rng>(&mut self, rng: &mut R) {
+        let Some(holders) = self.scenario.permits.and_then(|permits| permits.holders) else {
+            return;
+        };
+        let classes = self
+            .scenario
+            .classes
+            .iter()
+            .enumerate()
+            .filter(|(_, class)| class.permit)
+            .collect::<Vec<_>>();
+        if classes.is_empty() {
+            return;
+        }
+
+        for day in (0..self.steps).step_by(DAY as usize) {
+            for _ in 0..holders.sold {
+                if rng.random::<f32>() >= holders.show_up {
+                    continue;
+                }
+                let &(class_index, class) = classes
+                    .choose_weighted(rng, |(_, class)| class.cars_per_second)
+                    .unwrap();
+                let (from, to) = class.hours.unwrap_or((0, DAY));
+                let window = (to + DAY - from - 1) % DAY + 1;
+                let time = day + (from + rng.random_range(0..window)) % DAY;
+                if time < self.steps {
+                    self.arrivals.push(Arrival {
+                        time,
+                        class: class_index,
+                    });
+                }
+            }
+        }
+    }
+
+    /// Finds the pool that a car would park in, if the admission policy lets it in
+    /// and any of its pools have space for its vehicle.
+    #[inline]
+    fn find_spot(&self, class: usize, vehicle: usize) -> Option<usize> {
+        if let Some(admission) = &self.scenario.admission {
+            let units = self.scenario.vehicles[vehicle].units;
+            if !admission.admits(
+                class,
+                units,
+                self.usable_space(class, vehicle),
+                self.lot.occupancy(),
+            ) {
+                return None;
+            }
+        }
+        self.find_free_spot(class, vehicle)
+    }
+
+    /// Finds the pool that a car would park in, if any of its pools have space for its vehicle.
+    /// Permit holders try the reserved spots first, while walk-ins can only use them
+    /// once they have been released.
+    #[inline]
+    fn find_free_spot(&self, class: usize, vehicle: usize) -> Option<usize> {
+        let vehicle = &self.scenario.vehicles[vehicle];
+        let (pools, units) = (&vehicle.pools, vehicle.units);
+        let Some(permits) = self.scenario.permits else {
+            return self.lot.find_spot(pools, units);
+        };
+
+        if self.scenario.classes[class].permit {
+            self.lot
+                .find_spot(&[permits.pool], units)
+                .or_else(|| self.lot.find_spot(pools, units))
+        } else if permits.released(self.clock) {
+            self.lot
+                .find_spot(pools, units)
+                .or_else(|| self.lot.find_spot(&[permits.pool], units))
+        } else {
+            self.lot.find_spot(pools, units)
+        }
+    }
+
+    /// The free space in the pools that a car could park in, in units of space.
+    /// This includes the reserved spots for permit holders, and for walk-ins once they have been released.
+    fn usable_space(&self, class: usize, vehicle: usize) -> usize {
+        let free = self.lot.free(&self.scenario.vehicles[vehicle].pools);
+        match self.scenario.permits {
+            Some(permits)
+                if self.scenario.classes[class].permit || permits.released(self.clock) =>
+            {
+                free.saturating_add(self.lot.free(&[permits.pool]))
             }
-            self.arrival_times.push(clock);
-            // Advance by one additional second so that no two cars arrive at the same time
-            clock = clock.saturating_add(1);
+            _ => free,
+        }
+    }
+
+    /// Whether every queued car can park as soon as any of them can,
+    /// which is the case with only one vehicle type and no reserved spots.
+    fn interchangeable(&self) -> bool {
+        self.scenario.vehicles.len() == 1
+            && self.scenario.permits.is_none()
+            && self
+                .scenario
+                .admission
+                .as_ref()
+                .is_none_or(|admission| admission.reserve == 0)
+    }
+
+    /// Finds the position of the car in the queue that parks next, according to the queue discipline.
+    /// Only cars that have a spot available to them are considered,
+    /// so cars further back in the queue may park if the cars in front of them can't.
+    fn next_parkable<R: rand::Rng>(&self, rng: &mut R) -> Option<usize> {
+        let all = self.interchangeable();
+        if (all || !self.is_open()) && !self.can_park() {
+            return None;
+        }
+        let mut parkable = self
+            .incoming
+            .iter()
+            .enumerate()
+            .filter(|(_, car)| all || self.find_spot(car.class, car.vehicle).is_some());
+
+        match self.scenario.discipline {
+            QueueDiscipline::Fifo if all => Some(0),
+            QueueDiscipline::Fifo => parkable.next().map(|(index, _)| index),
+            QueueDiscipline::Lifo => parkable.next_back().map(|(index, _)| index),
+            QueueDiscipline::Random if all => Some(rng.random_range(0..self.incoming.len())),
+            QueueDiscipline::Random => parkable.map(|(index, _)| index).choose(rng),
+            QueueDiscipline::Priority => parkable
+                .max_by_key(|&(index, car)| {
+                    (self.scenario.classes[car.class].priority, Reverse(index))
+                })
+                .map(|(index, _)| index),
         }
     }
 
+    /// Whether the lot is letting cars in.
+    #[inline]
+    fn is_open(&self) -> bool {
+        self.scenario
+            .opening_hours
+            .is_none_or(|hours| hours.is_open(self.clock))
+    }
+
+    /// Whether any car in the queue has a spot available to it.
     fn can_park(&self) -> bool {
-        self.occupancy != self.capacity
+        if !self.is_open() {
+            return false;
+        }
+        if self.interchangeable() {
+            return self
+                .incoming
+                .front()
+                .is_some_and(|car| self.find_spot(car.class, car.vehicle).is_some());
+        }
+
+        self.incoming
+            .iter()
+            .any(|car| self.find_spot(car.class, car.vehicle).is_some())
+    }
+
+    /// Takes the next car in the queue that can park and gives it a spot,
+    /// returning the car and its spot.
+    fn admit_car<R: rand::Rng>(&mut self, rng: &mut R) -> Option<(QueuedCar, usize)> {
+        let car = self.incoming.remove(self.next_parkable(rng)?)?;
+        let pool = self.find_spot(car.class, car.vehicle)?;
+        let units = self.scenario.vehicles[car.vehicle].units;
+        let spot = self.lot.park(rng, pool, units, self.clock);
+        if let Some(spots) = self.lot.spots() {
+            self.stats.walked += 1;
+            self.stats.walking_distance += f64::from(spots.distance(spot));
+        }
+        if let Some(permits) = self.scenario.permits
+            && pool == permits.pool
+            && !self.scenario.classes[car.class].permit
+        {
+            self.stats.released_parked += 1;
+        }
+
+        let wait_time = self.observed(car.arrival_time, self.clock);
+        let class = &mut self.stats.classes[car.class];
+        class.wait_time += wait_time;
+        class.longest_wait = class.longest_wait.max(wait_time);
+        self.stats.pools[self.scenario.vehicles[car.vehicle].pools[0]].wait_time += wait_time;
+        Some((car, spot))
     }
 
-    /// Generates a departure time for a car, add it to the [`Self::departure_times`] list.
-    /// Does **not** change [`Self::incoming`].
-    /// This function assumes that the caller has already checked that the lot is not full.
-    fn park_car<R: rand::Rng>(&mut self, rng: &mut R) {
-        // Generate a departure time and add it to the heap
-        let departure_time = self.clock + self.pdf.sample(rng);
-        self.occupancy += 1;
-        self.departure_times.push(Reverse(departure_time));
+    /// Generates a departure time for a car that has just parked,
+    /// and adds it to the [`Self::departure_times`] list.
+    /// The car must have already been given a spot by [`Self::admit_car`].
+    ///
+    /// EVs parked at a charger stay for as long as they need to charge instead,
+    /// unless they move to a regular spot afterwards.
+    ///
+    /// Drivers going to a special event leave together once it ends, whatever the price.
+    fn park_car<R: rand::Rng>(&mut self, rng: &mut R, car: QueuedCar, spot: usize) {
+        let scenario = self.scenario;
+        let (class, vehicle) = (car.class, car.vehicle);
+        let pool = self.lot.pool(spot);
+        // Generate a departure time, with drivers staying for less time when the price is higher
+        let multiplier = self.price_multiplier();
+        let mut departure_time = if car.leave_by == u32::MAX {
+            let mut stay = scenario.classes[class].sample_stay(rng, self.clock);
+            if let Some(pricing) = &scenario.pricing {
+                stay = pricing.scale_stay(stay, multiplier);
+            }
+            self.clock + stay
+        } else {
+            EventSpec::departure(rng, car.leave_by).max(self.clock + 1)
+        };
+        // The time at which the car moves off its charger, if it does
+        let mut unplug_time = None;
+
+        if let Some(charging) = &scenario.vehicles[vehicle].charging {
+            let power = scenario.pools[pool].power;
+            if power > 0.0 {
+                let session = charging.session(rng, power);
+                self.stats.charged += 1;
+                self.stats.charge_time += u64::from(session.charge_time);
+                self.stats.energy += u64::from(session.energy);
+
+                let unplugged = self.clock + session.plugged_in;
+                match charging.after {
+                    AfterCharging::Leave => departure_time = unplugged,
+                    AfterCharging::Move if unplugged < departure_time => {
+                        unplug_time = Some(unplugged);
+                    }
+                    AfterCharging::Move => {}
+                }
+            } else {
+                self.stats.uncharged += 1;
+            }
+        }
+
+        // Drivers can't stay past closing time, unless they are allowed to overstay
+        if let Some(hours) = scenario.opening_hours
+            && matches!(hours.at_closing, AtClosing::Leave)
+            && let Some(closing) = hours.next_closing(self.clock)
+            && departure_time > closing
+        {
+            departure_time = closing;
+            unplug_time = unplug_time.filter(|&unplugged| unplugged < closing);
+            self.stats.forced_departures += 1;
+        }
+
+        // Add the departure (or the move to a regular spot) to the heap
+        let car = self.add_car(ParkedCar {
+            class,
+            vehicle,
+            spot,
+            since: self.clock,
+            price: multiplier * scenario.pools[pool].price,
+        });
+        if let Some(unplugged) = unplug_time {
+            self.moves.push(Reverse((unplugged, departure_time, car)));
+        } else {
+            self.departure_times
+                .push(Reverse(Departure::new(departure_time, car)));
+        }
+
+        let spot_time = self.spot_time(vehicle, self.clock, departure_time);
+        let stats = &mut self.stats.classes[class];
+        stats.parked += 1;
+        stats.spot_time += spot_time;
+        let spot_time = self.spot_time(vehicle, self.clock, unplug_time.unwrap_or(departure_time));
+        let stats = &mut self.stats.pools[pool];
+        stats.parked += 1;
+        stats.spot_time += spot_time;
     }
 
-    /// Handles any departures for a given timestamp.
+    /// The number of seconds between two timestamps that are within the observed part of the simulation,
+    /// i.e. after the warm-up period and before the end.
+    #[inline]
+    fn observed(&self, from: u32, to: u32) -> u64 {
+        u64::from(
+            to.min(self.steps)
+                .saturating_sub(from.max(self.scenario.warm_up)),
+        )
+    }
+
+    /// Keeps track of a car that has just parked, returning its index in [`Self::cars`].
+    fn add_car(&mut self, car: ParkedCar) -> usize {
+        if let Some(index) = self.free_cars.pop() {
+            self.cars[index] = Some(car);
+            index
+        } else {
+            self.cars.push(Some(car));
+            self.cars.len() - 1
+        }
+    }
+
+    /// Frees the spot of a car that is leaving, and charges the driver for their time in it.
+    /// Cars that parked during the warm-up period were discarded from the statistics, so they aren't charged.
+    fn leave(&mut self, car: usize, time: u32) {
+        let Some(parked) = self.cars[car].take() else {
+            unreachable!("car {car} left twice");
+        };
+        self.free_cars.push(car);
+        self.lot.leave(
+            parked.spot,
+            self.scenario.vehicles[parked.vehicle].units,
+            time,
+        );
+        if parked.since >= self.scenario.warm_up {
+            self.stats.classes[parked.class].revenue += parked.fare(self.scenario, time);
+        }
+    }
+
+    /// The number of spot-seconds that a vehicle takes up between two timestamps,
+    /// within the observed part of the simulation.
+    /// Vehicles that take up part of a spot (or several spots) count for that fraction of the time.
+    #[inline]
+    fn spot_time(&self, vehicle: usize, from: u32, to: u32) -> u64 {
+        let units = self.scenario.vehicles[vehicle].units as u64;
+        self.observed(from, to) * units / UNITS_PER_SPOT as u64
+    }
+
+    /// The multiplier of the current price, which only changes with dynamic pricing.
+    /// Drivers pay the price at the time that they park.
+    fn price_multiplier(&self) -> f32 {
+        self.scenario.pricing.as_ref().map_or(1.0, |pricing| {
+            pricing.surge.multiplier(self.lot.occupancy())
+        })
+    }
+
+    /// Moves any EVs that are done charging to a regular spot.
+    /// EVs that can't find one stay at their charger until they leave.
+    fn handle_moves<R: rand::Rng>(&mut self, rng: &mut R) {
+        while let Some(&Reverse((time, departure_time, car))) = self.moves.peek() {
+            if time > self.clock {
+                break;
+            }
+
+            self.moves.pop();
+            let Some(ParkedCar {
+                vehicle,
+                spot: charger,
+                ..
+            }) = self.cars[car]
+            else {
+                unreachable!("car {car} moved after leaving");
+            };
+            let vehicle_type = &self.scenario.vehicles[vehicle];
+            let units = vehicle_type.units;
+            let spot = if let Some(pool) = self.lot.find_spot(&vehicle_type.move_to, units) {
+                self.lot.leave(charger, units, self.clock);
+                self.stats.moved += 1;
+                self.stats.pools[pool].parked += 1;
+                self.lot.park(rng, pool, units, self.clock)
+            } else {
+                self.stats.stayed_plugged_in += 1;
+                charger
+            };
+            if let Some(parked) = &mut self.cars[car] {
+                parked.spot = spot;
+            }
+            self.stats.pools[self.lot.pool(spot)].spot_time +=
+                self.spot_time(vehicle, time, departure_time);
+            self.departure_times
+                .push(Reverse(Departure::new(departure_time, car)));
+        }
+    }
+
+    /// Parks any cars that are done being served by the entrance gates,
+    /// and starts serving queued cars at any free gates while there are spots for them.
+    fn handle_entry_gates<R: rand::Rng>(&mut self, rng: &mut R) {
+        let Some(config) = self.scenario.entry_gates else {
+            return;
+        };
+
+        while let Some((car, spot)) = self
+            .entry_gates
+            .as_mut()
+            .and_then(|g| g.complete(self.clock))
+        {
+            self.park_car(rng, car, spot);
+        }
+
+        while self.entry_gates.as_ref().is_some_and(Gates::is_free)
+            && let Some((car, spot)) = self.admit_car(rng)
+        {
+            let done_time = self.clock + config.service.sample(rng);
+            if let Some(gates) = &mut self.entry_gates {
+                gates.start(done_time, (car, spot));
+            }
+        }
+    }
+
+    /// Adds any cars arriving at the current timestamp to the queue,
+    /// unless their drivers decide not to join it.
+    fn handle_arrivals<R: rand::Rng>(&mut self, rng: &mut R) {
+        while let Some(arrival) = self.arrivals.get(self.arrival_index) {
+            if arrival.time != self.clock {
+                break;
+            }
+
+            let class = arrival.class;
+            self.arrival_index += 1;
+            self.stats.classes[class].arrived += 1;
+
+            // Drivers going to a special event that has already ended don't come at all
+            let leave_by = self.event_ends[class]
+                .get((self.clock / DAY) as usize)
+                .copied()
+                .unwrap_or(u32::MAX);
+            if self.clock >= leave_by {
+                self.stats.classes[class].missed_event += 1;
+                continue;
+            }
+
+            if let Some(pricing) = &self.scenario.pricing
+                && !pricing.accepts(rng, self.price_multiplier())
+            {
+                self.stats.classes[class].priced_out += 1;
+                continue;
+            }
+            let vehicle = vehicle::sample(rng, &self.scenario.vehicles);
+
+            if let Some(permits) = self.scenario.permits
+                && self.scenario.classes[class].permit
+                && self
+                    .lot
+                    .find_spot(&[permits.pool], self.scenario.vehicles[vehicle].units)
+                    .is_none()
+            {
+                self.stats.no_permit_spot += 1;
+            }
+            if self.scenario.admission.is_some()
+                && self.find_spot(class, vehicle).is_none()
+                && self.find_free_spot(class, vehicle).is_some()
+            {
+                self.stats.classes[class].held_back += 1;
+            }
+
+            // Drivers who find the lot full circle the block instead of queueing
+            if let Some(cruising) = self.scenario.cruising
+                && self.find_spot(class, vehicle).is_none()
+            {
+                self.stats.cruised += 1;
+                self.cruising.push(Reverse(CruisingCar {
+                    retry_time: self.clock + cruising.delay(rng),
+                    since: self.clock,
+                    retries: 0,
+                    class,
+                    vehicle,
+                    leave_by,
+                }));
+                continue;
+            }
+
+            if let Some(balking) = self.scenario.balking
+                && balking.balks(
+                    rng,
+                    self.incoming.len(),
+                    self.find_spot(class, vehicle).is_none(),
+                )
+            {
+                self.stats.classes[class].balked += 1;
+                continue;
+            }
+
+            // Cars that can park right away drive straight through the lane, even when it is full
+            if let Some(storage) = self.scenario.queue_storage
+                && self.incoming.len() >= storage
+                && self.find_spot(class, vehicle).is_none()
+                && matches!(self.scenario.overflow, Overflow::TurnAway)
+            {
+                self.stats.classes[class].turned_away += 1;
+                continue;
+            }
+
+            self.incoming.push(QueuedCar {
+                arrival_time: self.clock,
+                class,
+                vehicle,
+                deadline: EntranceQueue::deadline(rng, self.clock, self.scenario.patience)
+                    .min(leave_by),
+                leave_by,
+            });
+            self.stats.record_queue(self.incoming.len());
+        }
+    }
+
+    /// Lets any cruising drivers that are due try the lot again.
+    /// Drivers that find a free spot join the queue, while the rest go round again or give up.
+    fn handle_cruising<R: rand::Rng>(&mut self, rng: &mut R) {
+        let Some(cruising) = self.scenario.cruising else {
+            return;
+        };
+
+        while let Some(&Reverse(car)) = self.cruising.peek() {
+            if car.retry_time > self.clock {
+                break;
+            }
+
+            self.cruising.pop();
+            self.stats.retries += 1;
+            self.stats.cruise_time += self.observed(car.since, self.clock);
+            if self.clock >= car.leave_by {
+                self.stats.classes[car.class].missed_event += 1;
+            } else if self.is_open() && self.find_spot(car.class, car.vehicle).is_some() {
+                self.incoming.push(QueuedCar {
+                    arrival_time: self.clock,
+                    class: car.class,
+                    vehicle: car.vehicle,
+                    deadline: EntranceQueue::deadline(rng, self.clock, self.scenario.patience)
+                        .min(car.leave_by),
+                    leave_by: car.leave_by,
+                });
+                self.stats.record_queue(self.incoming.len());
+            } else if cruising
+                .max_retries
+                .is_some_and(|max_retries| car.retries + 1 >= max_retries)
+            {
+                self.stats.classes[car.class].gave_up += 1;
+            } else {
+                self.cruising.push(Reverse(CruisingCar {
+                    retry_time: self.clock + cruising.delay(rng),
+                    since: self.clock,
+                    retries: car.retries + 1,
+                    ..car
+                }));
+            }
+        }
+    }
+
+    /// Removes any cars from the queue whose drivers have run out of patience,
+    /// or whose special event has ended.
+    fn handle_reneging(&mut self) {
+        let stats = &mut self.stats.classes;
+        let warm_up = self.scenario.warm_up;
+        self.incoming.renege(self.clock, |car| {
+            let class = &mut stats[car.class];
+            let wait_time = u64::from(car.deadline.saturating_sub(car.arrival_time.max(warm_up)));
+            if car.deadline >= car.leave_by {
+                class.missed_event += 1;
+            } else {
+                class.reneged += 1;
+            }
+            class.wait_time += wait_time;
+            class.longest_wait = class.longest_wait.max(wait_time);
+        });
+    }
+
+    /// Handles any departures up to a given timestamp.
+    /// If there are exit gates, departing cars join the exit queue instead of leaving right away.
     fn handle_departures(&mut self, timestamp: u32) {
-        while let Some(&Reverse(time)) = self.departure_times.peek() {
-            if time != timestamp {
+        while let Some(&Reverse(departure)) = self.departure_times.peek() {
+            let time = departure.time();
+            if time > timestamp {
                 break;
             }
 
             self.departure_times.pop();
-            self.occupancy -= 1;
+            if self.exit_gates.is_some() {
+                self.outgoing.push_back(departure);
+            } else {
+                self.leave(departure.car(), time);
+            }
         }
+
+        self.stats.longest_exit_queue = self
+            .stats
+            .longest_exit_queue
+            .max(self.outgoing.len() as u64);
+    }
+
+    /// Frees the spots of any cars that are done being served by the exit gates,
+    /// and starts serving cars in the exit queue at any free gates.
+    fn handle_exit_gates<R: rand::Rng>(&mut self, rng: &mut R) {
+        let Some(config) = self.scenario.exit_gates else {
+            return;
+        };
+
+        while let Some(departure) = self
+            .exit_gates
+            .as_mut()
+            .and_then(|g| g.complete(self.clock))
+        {
+            self.leave(departure.car(), self.clock);
+            self.stats.exited += 1;
+            self.stats.exit_time += u64::from(self.clock - departure.time());
+        }
+
+        let Some(gates) = &mut self.exit_gates else {
+            return;
+        };
+        while gates.is_free()
+            && let Some(car) = self.outgoing.pop_front()
+        {
+            gates.start(self.clock + config.service.sample(rng), car);
+        }
+    }
+
+    /// Sends away the cars still in the queue when the lot closes,
+    /// and counts the cars that are still parked if they are allowed to overstay.
+    fn handle_closing(&mut self) {
+        let Some(hours) = self.scenario.opening_hours else {
+            return;
+        };
+        while let Some(closing) = self.next_closing.filter(|&closing| closing <= self.clock) {
+            while let Some(car) = self.incoming.remove(0) {
+                let wait_time = self.observed(car.arrival_time, self.clock);
+                let class = &mut self.stats.classes[car.class];
+                class.closed_out += 1;
+                class.wait_time += wait_time;
+                class.longest_wait = class.longest_wait.max(wait_time);
+            }
+            while let Some(Reverse(car)) = self.cruising.pop() {
+                self.stats.cruise_time += self.observed(car.since, self.clock);
+                self.stats.classes[car.class].closed_out += 1;
+            }
+            if matches!(hours.at_closing, AtClosing::Overstay) {
+                self.stats.overstays += self.lot.occupied() as u64;
+            }
+            self.next_closing = hours.next_closing(closing);
+        }
+    }
+
+    /// Closes or reopens any spots that are scheduled to change by now.
+    /// Cars parked in closed spots stay until they leave.
+    fn handle_capacity_changes(&mut self) {
+        while let Some(change) = self.schedule.due(self.clock) {
+            self.lot.close(change.pool, change.closed);
+        }
+    }
+
+    /// The time of the next arrival, departure, driver giving up, car done at a gate,
+    /// EV moving off its charger, scheduled capacity change, closing time, or cruising driver trying again,
+    /// if there are any left.
+    /// Cars waiting for an exit gate don't need their own events,
+    /// since they can only start being served once another car is done.
+    fn next_event_time(&self) -> Option<u32> {
+        let next_arrival = self.arrivals.get(self.arrival_index).map(|a| a.time);
+        let next_departure = self.departure_times.peek().map(|r| r.0.time());
+        next_arrival
+            .into_iter()
+            .chain(next_departure)
+            .chain(self.incoming.next_deadline())
+            .chain(self.entry_gates.as_ref().and_then(Gates::next_completion))
+            .chain(self.exit_gates.as_ref().and_then(Gates::next_completion))
+            .chain(self.moves.peek().map(|r| r.0.0))
+            .chain(self.schedule.next_time())
+            .chain(self.next_closing)
+            .chain(self.cruising.peek().map(|r| r.0.retry_time))
+            .min()
+    }
+
+    /// Moves the clock forward to a given timestamp,
+    /// keeping track of how long the queue spent spilling out of the entrance lane.
+    fn advance_clock(&mut self, timestamp: u32) {
+        if !self.warmed_up && timestamp >= self.scenario.warm_up {
+            self.advance_clock_inner(self.scenario.warm_up);
+            self.end_warm_up();
+        }
+        self.advance_clock_inner(timestamp);
+    }
+
+    fn advance_clock_inner(&mut self, timestamp: u32) {
+        self.stats
+            .record_queue_time(self.incoming.len(), u64::from(timestamp - self.clock));
+        if let Some(storage) = self.scenario.queue_storage
+            && self.incoming.len() > storage
+        {
+            self.stats.spillback_time += u64::from(timestamp - self.clock);
+        }
+        if let Some(admission) = &self.scenario.admission
+            && admission.sign_on(self.lot.occupancy())
+        {
+            self.stats.sign_time += u64::from(timestamp - self.clock);
+        }
+        if let Some((interval, trace)) = &mut self.trace {
+            // The occupancy doesn't change between events
+            let occupied = self.lot.occupied().min(u32::MAX as usize) as u32;
+            while (trace.len() as u32 + 1) * *interval <= timestamp {
+                trace.push(occupied);
+            }
+        }
+        self.clock = timestamp;
+    }
+
+    /// Discards the statistics from the warm-up period.
+    /// Spot time is only ever counted after the warm-up period, so it is kept.
+    fn end_warm_up(&mut self) {
+        let mut stats = RunStats::new(self.scenario.classes.len(), self.scenario.pools.len());
+        for (class, old) in stats.classes.iter_mut().zip(&self.stats.classes) {
+            class.spot_time = old.spot_time;
+        }
+        for (pool, old) in stats.pools.iter_mut().zip(&self.stats.pools) {
+            pool.spot_time = old.spot_time;
+        }
+        stats.record_queue(self.incoming.len());
+        self.stats = stats;
+        self.warmed_up = true;
+    }
+
+    /// Starts sampling the number of occupied spots at a fixed interval.
+    pub fn trace_occupancy(&mut self, interval: u32) {
+        self.trace = Some((interval, Vec::new()));
+    }
+
+    /// The number of occupied spots at each multiple of the interval given to [`Self::trace_occupancy`].
+    pub fn occupancy_trace(&self) -> &[u32] {
+        self.trace.as_ref().map_or(&[], |(_, trace)| trace)
+    }
+
+    /// Returns the statistics for this run.
+    /// Cars still in the queue are counted as having waited until the end of the simulation,
+    /// and cars still parked are charged for their time in the lot up to the end.
+    pub fn stats(&self) -> RunStats {
+        let mut stats = self.stats.clone();
+        for car in self.cars.iter().flatten() {
+            if car.since >= self.scenario.warm_up {
+                stats.classes[car.class].revenue += car.fare(self.scenario, self.steps);
+            }
+        }
+        for car in self.incoming.iter() {
+            let wait_time = self.observed(car.arrival_time, self.steps);
+            let class = &mut stats.classes[car.class];
+            class.queued += 1;
+            class.wait_time += wait_time;
+            class.longest_wait = class.longest_wait.max(wait_time);
+            let pool = &mut stats.pools[self.scenario.vehicles[car.vehicle].pools[0]];
+            pool.queued += 1;
+            pool.wait_time += wait_time;
+        }
+        for Reverse(car) in &self.cruising {
+            stats.cruise_time += self.observed(car.since, self.steps);
+        }
+        if let Some(spots) = self.lot.spots() {
+            stats.spot_busy = spots.busy_time(self.steps);
+        }
+        stats
     }
 }
 
-impl Simulator for EventSimulator {
+impl Simulator for EventSimulator<'_> {
     fn simulate<T: rand::Rng>(&mut self, rng: &mut T) {
         // Whether there are currently any queued cars, and the lot has space for them to park.
         // Used to make sure that we don't skip time when there is an empty spot available
@@ -100,61 +1054,313 @@ impl Simulator for EventSimulator {
         let mut cars_can_park = false;
 
         while self.clock < self.steps {
-            let next_arrival = self.arrival_times.get(self.arrival_index).copied();
-            let next_departure = self.departure_times.peek().map(|r| r.0);
-
-            // Find the next event and its timestamp
-            let (mut next_time, mut has_arrival, mut has_departure) =
-                match (next_arrival, next_departure) {
-                    // Both an arrival and departure are available; pick the one that comes first
-                    (Some(arrival), Some(departure)) => {
-                        let min = arrival.min(departure);
-                        (min, arrival == min, departure == min)
-                    }
-                    (Some(arrival), None) => (arrival, true, false),
-                    (None, Some(departure)) => (departure, false, true),
-                    // No events left; end the simulation
-                    (None, None) => break,
-                };
-
             // If there are already cars waiting to park, and we have an empty spot,
             // then just increase the clock by one tick
-            if cars_can_park {
-                let actual_next_time = self.clock + 1;
-                // If the next event doesn't also happen at this time, then there will not be an
-                // arrival nor a departure
-                if next_time != actual_next_time {
-                    has_arrival = false;
-                    has_departure = false;
-                }
-                next_time = actual_next_time;
-            }
+            let next_time = if cars_can_park {
+                Some(self.clock + 1)
+            } else {
+                self.next_event_time()
+            };
 
-            self.clock = next_time;
+            // No events left; end the simulation
+            let Some(next_time) = next_time.filter(|&time| time < self.steps) else {
+                break;
+            };
+            self.advance_clock(next_time);
 
-            // If we have an arrival, add a car to the queue and move to the next arrival time
-            if has_arrival {
-                self.arrival_index += 1;
-                self.incoming += 1;
-            }
+            // Add any arriving cars to the queue, and handle any departures for this timestamp
+            self.handle_arrivals(rng);
+            self.handle_cruising(rng);
+            self.handle_departures(self.clock);
+            self.handle_exit_gates(rng);
+            self.handle_moves(rng);
+            self.handle_capacity_changes();
+            self.handle_reneging();
+            self.handle_closing();
 
-            // Handle any departures for this timestamp
-            if has_departure {
-                self.handle_departures(self.clock);
+            if self.entry_gates.is_some() {
+                self.handle_entry_gates(rng);
             }
-
             // Park a car in the queue if there is space
-            if self.incoming != 0 && self.can_park() {
-                self.park_car(rng);
-                self.incoming -= 1;
+            else if let Some((car, spot)) = self.admit_car(rng) {
+                self.park_car(rng, car, spot);
                 // If there are more cars and we have space for them to park,
                 // set this flag so that we don't accidentally skip too much time
-                cars_can_park = self.incoming != 0 && self.can_park();
+                cars_can_park = self.can_park();
+            } else {
+                // Nothing can park until the next event, so there is no need to step one tick at a time
+                cars_can_park = false;
             }
         }
+
+        self.advance_clock(self.steps);
     }
 
+    /// Cars that were still in the queue when the lot closed,
+    /// and cars that are still cruising for a spot, count as being left in the queue too.
     fn cars_left(&self) -> usize {
-        self.incoming
+        self.incoming.len()
+            + self.cruising.len()
+            + self
+                .stats
+                .classes
+                .iter()
+                .map(|class| class.closed_out as usize)
+                .sum::<usize>()
+    }
+
+    fn cars_lost(&self) -> usize {
+        self.stats
+            .classes
+            .iter()
+            .map(|class| class.lost() as usize)
+            .sum()
+    }
+
+    fn wait_time(&self) -> u64 {
+        let queued = self
+            .incoming
+            .iter()
+            .map(|car| self.observed(car.arrival_time, self.steps))
+            .sum::<u64>();
+        self.stats
+            .classes
+            .iter()
+            .map(|class| class.wait_time)
+            .sum::<u64>()
+            + queued
+    }
+
+    fn permit_refusals(&self) -> (usize, usize) {
+        let arrived = self
+            .scenario
+            .classes
+            .iter()
+            .zip(&self.stats.classes)
+            .filter(|(class, _)| class.permit)
+            .map(|(_, stats)| stats.arrived as usize)
+            .sum();
+        (self.stats.no_permit_spot as usize, arrived)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use clap::Parser;
+    use rand::{SeedableRng, rngs::StdRng};
+
+    use super::*;
+    use crate::cli::Cli;
+
+    /// Simulates a day of a lot with a given number of spots, returning its statistics.
+    /// The arguments start with the number of cars per hour.
+    fn simulate(spots: usize, args: &[&str]) -> RunStats {
+        let mut cli = vec!["test", "-e"];
+        cli.extend(args);
+        let scenario = Scenario::new(&Cli::parse_from(cli)).unwrap();
+        let mut rng = StdRng::seed_from_u64(1);
+        let mut sim = EventSimulator::new(spots, &scenario, &mut rng);
+        sim.simulate(&mut rng);
+        sim.stats()
+    }
+
+    #[test]
+    fn drivers_balk_instead_of_queueing_for_a_full_lot() {
+        let stats = simulate(20, &["60", "--balk-when-full", "1"]);
+        let class = &stats.classes[0];
+        assert!(class.balked > 0);
+        // Nobody joins the queue for a full lot, so every car either parks or balks
+        assert_eq!(class.queued, 0);
+        assert_eq!(class.parked + class.balked, class.arrived);
+    }
+
+    #[test]
+    fn impatient_drivers_renege() {
+        let patient = simulate(20, &["60", "--patience", "36000"]);
+        let impatient = simulate(20, &["60", "--patience", "600"]);
+        assert!(impatient.classes[0].reneged > patient.classes[0].reneged);
+        for stats in [patient, impatient] {
+            let class = &stats.classes[0];
+            assert_eq!(class.parked + class.reneged + class.queued, class.arrived);
+        }
+    }
+
+    #[test]
+    fn only_cars_that_have_to_queue_are_turned_away() {
+        let lane = ["--queue-storage", "1", "--max-lost", "1"];
+        // A lot that never fills up lets every car straight through the lane
+        let roomy = simulate(1000, &[&["60"], &lane[..]].concat());
+        assert_eq!(roomy.classes[0].turned_away, 0);
+        assert_eq!(roomy.classes[0].parked, roomy.classes[0].arrived);
+
+        let full = simulate(20, &[&["60"], &lane[..]].concat());
+        let class = &full.classes[0];
+        assert!(class.turned_away > 0);
+        assert!(full.longest_queue <= 1);
+        assert_eq!(
+            class.parked + class.turned_away + class.queued,
+            class.arrived
+        );
+    }
+
+    #[test]
+    fn vehicles_only_park_in_their_pools() {
+        let pools = ["--pool", "regular", "--pool", "compact,share=0.2"];
+        let cars = simulate(
+            50,
+            &[
+                &["60"],
+                &pools[..],
+                &["--vehicle", "car,share=1,pools=regular"],
+            ]
+            .concat(),
+        );
+        assert!(cars.pools[0].parked > 0);
+        assert_eq!(cars.pools[1].parked, 0);
+        assert_eq!(cars.pools[1].spot_time, 0);
+
+        // Small cars prefer the compact spots, but fall back to the regular ones when those are full
+        let small = simulate(
+            50,
+            &[
+                &["60"],
+                &pools[..],
+                &["--vehicle", "small,share=1,pools=compact/regular"],
+            ]
+            .concat(),
+        );
+        assert!(small.pools[0].parked > 0);
+        assert!(small.pools[1].parked > 0);
+        // Only the compact pool is ever waited for, since it is the first choice of every car
+        assert_eq!(small.pools[0].wait_time, 0);
+        assert!(small.pools[1].wait_time > 0);
+    }
+
+    #[test]
+    fn reserved_spots_are_kept_for_permit_holders() {
+        let staff = [
+            "--class",
+            "staff,rate=15,hours=7-10,permit",
+            "--permits",
+            "100",
+        ];
+        let stats = simulate(1000, &[&["0"], &staff[..]].concat());
+        // With enough reserved spots, every permit holder gets one
+        assert!(stats.classes[0].parked > 0);
+        assert_eq!(stats.no_permit_spot, 0);
+        assert_eq!(stats.pools[0].parked, 0);
+        assert_eq!(stats.pools[1].parked, stats.classes[0].parked);
+
+        // Walk-ins queue for a full lot rather than taking a reserved spot, until they are released
+        let walk_ins = simulate(20, &["60", "--permits", "10"]);
+        assert!(walk_ins.classes[0].queued > 0);
+        assert_eq!(walk_ins.pools[1].parked, 0);
+        let released = simulate(20, &["60", "--permits", "10", "--release-after", "0"]);
+        assert!(released.released_parked > 0);
+        assert_eq!(released.pools[1].parked, released.released_parked);
+    }
+
+    #[test]
+    fn revenue_is_charged_for_the_time_parked() {
+        // Charging one unit per second makes the revenue the number of seconds that spots were taken
+        let stats = simulate(50, &["60", "--tariff", "3600,per=1"]);
+        let class = &stats.classes[0];
+        assert!(class.revenue > 0.0);
+        // Prices are calculated as `f32`s, so they are only exact to about one part in a million
+        let spot_time = class.spot_time as f64;
+        assert!((class.revenue - spot_time).abs() < 1e-6 * spot_time);
+
+        // The cap limits what each driver pays for a day
+        let capped = simulate(50, &["60", "--tariff", "3600,per=1,cap=600"]);
+        let class = &capped.classes[0];
+        assert!(class.revenue > 0.0);
+        assert!(class.revenue <= 600.0 * class.parked as f64);
+        assert!(class.revenue < stats.classes[0].revenue);
+    }
+
+    #[test]
+    fn closed_spots_are_not_parked_in() {
+        let open = simulate(20, &["60"]);
+        let closed = simulate(20, &["60", "--closure", "20"]);
+        assert_eq!(closed.classes[0].parked, 0);
+        assert_eq!(closed.classes[0].queued, closed.classes[0].arrived);
+
+        // Half of the spots close for the morning, so fewer cars get in over the day
+        let morning = simulate(20, &["60", "--closure", "10,hours=0-12"]);
+        assert!(morning.classes[0].parked > 0);
+        assert!(morning.classes[0].parked < open.classes[0].parked);
+    }
+
+    #[test]
+    fn cars_only_arrive_while_the_lot_is_open() {
+        let all_day = simulate(500, &["60"]);
+        let stats = simulate(500, &["60", "--open-hours", "8-18"]);
+        let class = &stats.classes[0];
+        // Ten hours of arrivals instead of 24
+        assert!(class.arrived * 2 < all_day.classes[0].arrived);
+        assert!(stats.forced_departures > 0);
+        assert_eq!(stats.overstays, 0);
+        // Nobody stays past 18:00, so spots are taken for at most ten hours per car
+        assert!(class.spot_time <= class.parked * 10 * 3600);
+
+        let overstay = simulate(
+            500,
+            &["60", "--open-hours", "8-18", "--at-closing", "overstay"],
+        );
+        assert!(overstay.overstays > 0);
+        assert_eq!(overstay.forced_departures, 0);
+    }
+
+    #[test]
+    fn reserved_space_is_kept_free_in_compatible_pools() {
+        let stats = simulate(
+            40,
+            &["60", "--class", "vip,rate=15,priority=1", "--reserve", "10"],
+        );
+        // Only the default traffic is held back for the reserve
+        assert!(stats.classes[0].held_back > 0);
+        assert_eq!(stats.classes[1].held_back, 0);
+
+        // Spots in a pool that the cars can't use don't count towards the reserve
+        let pools = simulate(
+            40,
+            &[
+                "60",
+                "--pool",
+                "regular",
+                "--pool",
+                "compact,share=0.5",
+                "--vehicle",
+                "car,share=1,pools=regular",
+                "--reserve",
+                "5",
+            ],
+        );
+        assert!(pools.classes[0].held_back > 0);
+        assert_eq!(pools.pools[1].parked, 0);
+    }
+
+    #[test]
+    fn event_cars_leave_together_when_it_ends() {
+        let event = [
+            "0",
+            "--event",
+            "concert,cars=300,arrive=18/19/20,leave=22/22.1/22.2",
+        ];
+        let stats = simulate(1000, &event);
+        let class = &stats.classes[0];
+        assert!(class.parked > 200);
+        // Every car arrives after 18:00, and leaves within 10 minutes of the end at 22:12 at the latest
+        assert!(class.spot_time <= class.parked * (4 * 3600 + 22 * 60));
+
+        // Everybody leaving at once is a rush for the exit gates
+        let gates = simulate(1000, &[&event[..], &["--exit-service", "5"]].concat());
+        assert!(gates.longest_exit_queue > 100);
+
+        // Nobody leaves before the end, so drivers who can't park by then miss the event
+        let small = simulate(100, &event);
+        let class = &small.classes[0];
+        assert_eq!(class.parked, 100);
+        assert_eq!(class.parked + class.missed_event, class.arrived);
     }
 }
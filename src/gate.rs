@@ -0,0 +1,87 @@
+use std::{cmp::Reverse, collections::BinaryHeap, str::FromStr};
+
+use crate::{customer_class::parse_triangular, triangular_distribution::TriangularPdfSampler};
+
+/// The time that it takes a gate to serve a single car.
+#[derive(Clone, Copy)]
+pub enum ServiceTime {
+    Fixed(u32),
+    Triangular(TriangularPdfSampler),
+}
+
+impl ServiceTime {
+    /// Samples a service time. Serving a car always takes at least one second.
+    pub fn sample<T: rand::Rng>(&self, rng: &mut T) -> u32 {
+        match self {
+            Self::Fixed(time) => *time,
+            Self::Triangular(pdf) => pdf.sample(rng),
+        }
+        .max(1)
+    }
+}
+
+impl FromStr for ServiceTime {
+    type Err = String;
+
+    /// Parses either a fixed number of seconds, or a triangular distribution as `MIN/MODE/MAX`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.contains('/') {
+            parse_triangular(s).map(Self::Triangular)
+        } else {
+            s.parse()
+                .map(Self::Fixed)
+                .map_err(|e| format!("invalid service time `{s}`: {e}"))
+        }
+    }
+}
+
+/// The configuration for a group of gates (e.g. ticket machines or barrier arms).
+#[derive(Clone, Copy)]
+pub struct GateConfig {
+    pub count: usize,
+    pub service: ServiceTime,
+}
+
+/// A group of identical gates that each serve one car at a time.
+/// Each car being served can carry some data (e.g. its class), which is returned once it is done.
+pub struct Gates<T: Ord + Copy> {
+    count: usize,
+    /// The times at which cars currently being served will be done, alongside their data.
+    in_service: BinaryHeap<Reverse<(u32, T)>>,
+}
+
+impl<T: Ord + Copy> Gates<T> {
+    pub fn new(count: usize) -> Self {
+        Self {
+            count,
+            in_service: BinaryHeap::with_capacity(count),
+        }
+    }
+
+    #[inline]
+    pub fn is_free(&self) -> bool {
+        self.in_service.len() < self.count
+    }
+
+    /// Starts serving a car at a free gate.
+    /// This function assumes that the caller has already checked that a gate is free.
+    pub fn start(&mut self, done_time: u32, data: T) {
+        self.in_service.push(Reverse((done_time, data)));
+    }
+
+    /// The time at which the next car being served will be done.
+    pub fn next_completion(&self) -> Option<u32> {
+        self.in_service.peek().map(|r| r.0.0)
+    }
+
+    /// Removes a car whose service is done by the given timestamp, returning its data.
+    pub fn complete(&mut self, timestamp: u32) -> Option<T> {
+        match self.in_service.peek() {
+            Some(&Reverse((time, data))) if time <= timestamp => {
+                self.in_service.pop();
+                Some(data)
+            }
+            _ => None,
+        }
+    }
+}
@@ -0,0 +1,205 @@
+use std::{collections::BTreeSet, str::FromStr};
+
+use clap::ValueEnum;
+
+/// How drivers pick a spot out of the free spots in a pool.
+#[derive(Clone, Copy, ValueEnum)]
+pub enum SpotChoice {
+    /// The free spot with the shortest walk to the entrance.
+    Nearest,
+    /// Any free spot, picked at random.
+    Random,
+    /// The first free spot found by driving along each row in turn, starting with the first row.
+    Scan,
+}
+
+/// The physical layout of the spots in the lot.
+///
+/// The format is `ROW_LENGTH[,level=SPOTS][,width=METRES][,aisle=METRES][,stairs=METRES]`.
+/// Spots are laid out in rows of `ROW_LENGTH` spots, with `level` spots on each level (all on one level by default).
+/// The pools are laid out in the order that they are given, so the first pool is nearest to the entrance.
+/// The walk from a spot to the pedestrian entrance, which is at the start of the first row of the ground level,
+/// is `width` metres per spot along the row, `aisle` metres per row, and `stairs` metres per level.
+#[derive(Clone, Copy)]
+pub struct Layout {
+    pub row_length: usize,
+    pub level_size: Option<usize>,
+    pub spot_width: f32,
+    pub row_spacing: f32,
+    pub level_distance: f32,
+}
+
+impl Layout {
+    /// The level, row (within the level) and column of a spot.
+    pub fn position(&self, spot: usize) -> (usize, usize, usize) {
+        let level_size = self.level_size.unwrap_or(usize::MAX);
+        let (level, index) = (spot / level_size, spot % level_size);
+        (level, index / self.row_length, index % self.row_length)
+    }
+
+    /// The distance in metres that a driver walks from a spot to the entrance.
+    pub fn distance(&self, spot: usize) -> f32 {
+        let (level, row, column) = self.position(spot);
+        level as f32 * self.level_distance
+            + row as f32 * self.row_spacing
+            + (column as f32 + 0.5) * self.spot_width
+    }
+}
+
+impl FromStr for Layout {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parts = s.split(',');
+        let row_length = parts.next().unwrap_or_default();
+        let mut layout = Self {
+            row_length: match row_length.parse() {
+                Ok(length) if length > 0 => length,
+                _ => return Err(format!("invalid row length `{row_length}`")),
+            },
+            level_size: None,
+            spot_width: 2.5,
+            row_spacing: 8.0,
+            level_distance: 30.0,
+        };
+
+        for part in parts {
+            let (key, value) = part
+                .split_once('=')
+                .ok_or_else(|| format!("expected KEY=VALUE, found `{part}`"))?;
+            if key == "level" {
+                layout.level_size = match value.parse() {
+                    Ok(size) if size > 0 => Some(size),
+                    _ => return Err(format!("invalid level size `{value}`")),
+                };
+                continue;
+            }
+            let distance = match value.parse::<f32>() {
+                Ok(distance) if distance >= 0.0 => distance,
+                _ => return Err(format!("invalid distance `{value}`")),
+            };
+            match key {
+                "width" => layout.spot_width = distance,
+                "aisle" => layout.row_spacing = distance,
+                "stairs" => layout.level_distance = distance,
+                _ => return Err(format!("unknown layout option `{key}`")),
+            }
+        }
+
+        Ok(layout)
+    }
+}
+
+/// Keeps track of which individual spots are taken in a lot with a [`Layout`].
+#[derive(Clone)]
+pub struct SpotMap {
+    choice: SpotChoice,
+    /// The pool of each spot.
+    pools: Vec<usize>,
+    /// The spots of each pool, in the order that drivers prefer them.
+    preferred: Vec<Vec<usize>>,
+    /// The free spots of each pool, as indices into [`Self::preferred`].
+    free: Vec<BTreeSet<usize>>,
+    /// The index into [`Self::preferred`] of each spot.
+    rank: Vec<usize>,
+    /// The distance from each spot to the entrance.
+    distances: Vec<f32>,
+    /// The time at which the car in each spot parked, if it is taken.
+    parked_at: Vec<Option<u32>>,
+    /// The number of seconds that each spot was taken for, within the observed part of the simulation.
+    busy: Vec<u64>,
+    /// The part of the simulation whose busy time is counted.
+    observed: (u32, u32),
+}
+
+impl SpotMap {
+    /// Lays out pools of the given sizes, one after the other.
+    pub fn new(layout: &Layout, choice: SpotChoice, sizes: &[usize], observed: (u32, u32)) -> Self {
+        let total = sizes.iter().sum::<usize>();
+        let distances = (0..total)
+            .map(|spot| layout.distance(spot))
+            .collect::<Vec<_>>();
+
+        let mut pools = Vec::with_capacity(total);
+        let mut preferred = Vec::with_capacity(sizes.len());
+        let mut rank = vec![0; total];
+        for (pool, &size) in sizes.iter().enumerate() {
+            let start = pools.len();
+            pools.extend(std::iter::repeat_n(pool, size));
+            let mut spots = (start..start + size).collect::<Vec<_>>();
+            if matches!(choice, SpotChoice::Nearest) {
+                spots.sort_by(|&a, &b| distances[a].total_cmp(&distances[b]));
+            }
+            for (index, &spot) in spots.iter().enumerate() {
+                rank[spot] = index;
+            }
+            preferred.push(spots);
+        }
+
+        Self {
+            choice,
+            pools,
+            free: sizes.iter().map(|&size| (0..size).collect()).collect(),
+            preferred,
+            rank,
+            distances,
+            parked_at: vec![None; total],
+            busy: vec![0; total],
+            observed,
+        }
+    }
+
+    /// Takes a free spot in a pool, returning the spot.
+    /// This function assumes that the pool has a free spot.
+    pub fn park<R: rand::Rng>(&mut self, rng: &mut R, pool: usize, timestamp: u32) -> usize {
+        let free = &mut self.free[pool];
+        let index = match self.choice {
+            SpotChoice::Nearest | SpotChoice::Scan => free.pop_first(),
+            SpotChoice::Random => {
+                let index = free.iter().nth(rng.random_range(0..free.len())).copied();
+                index.and_then(|index| free.take(&index))
+            }
+        }
+        .expect("There should be a free spot in the pool.");
+
+        let spot = self.preferred[pool][index];
+        self.parked_at[spot] = Some(timestamp);
+        spot
+    }
+
+    /// Frees a spot, returning its pool.
+    pub fn leave(&mut self, spot: usize, timestamp: u32) -> usize {
+        if let Some(parked_at) = self.parked_at[spot].take() {
+            self.busy[spot] += self.observed_time(parked_at, timestamp);
+        }
+        let pool = self.pools[spot];
+        self.free[pool].insert(self.rank[spot]);
+        pool
+    }
+
+    #[inline]
+    pub fn pool(&self, spot: usize) -> usize {
+        self.pools[spot]
+    }
+
+    #[inline]
+    pub fn distance(&self, spot: usize) -> f32 {
+        self.distances[spot]
+    }
+
+    /// The number of seconds that each spot was taken for, counting cars that are still parked up to a given time.
+    pub fn busy_time(&self, timestamp: u32) -> Vec<u64> {
+        self.busy
+            .iter()
+            .zip(&self.parked_at)
+            .map(|(&busy, parked_at)| {
+                busy + parked_at.map_or(0, |parked_at| self.observed_time(parked_at, timestamp))
+            })
+            .collect()
+    }
+
+    fn observed_time(&self, from: u32, to: u32) -> u64 {
+        let (start, end) = self.observed;
+        u64::from(to.min(end).saturating_sub(from.max(start)))
+    }
+}
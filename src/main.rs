@@ -5,7 +5,7 @@
     clippy::cast_possible_truncation,
     clippy::cast_sign_loss
 )]
-use clap::Parser;
+use clap::{CommandFactory, Parser, error::ErrorKind};
 use rayon::prelude::*;
 use std::{
     io::Write,
@@ -15,19 +15,47 @@ use std::{
 
 use faithful::FaithfulSimulator;
 #[allow(unused_imports)]
-use parking_lot::{ArrayParkingLot, VecParkingLot};
+use parking_lot::{ArrayParkingLot, ParkingLot, VecParkingLot};
 use simulator::{Simulator, StandardSimulator};
 
-use crate::{continuous_simulator::ContinuousHeapSimulator, event_simulator::EventSimulator};
+use crate::{
+    analytic::Estimate,
+    continuous_simulator::ContinuousHeapSimulator,
+    event_simulator::EventSimulator,
+    gate::GateConfig,
+    parking_lot::PoolSize,
+    permit::{PermitHolders, Permits},
+    scenario::Scenario,
+    schedule::Schedule,
+    warm_up::WarmUp,
+};
 
+mod admission;
+mod analytic;
+mod charging;
 mod cli;
 mod continuous_simulator;
+mod customer_class;
 mod event_simulator;
 mod faithful;
+mod gate;
+mod layout;
+mod network;
+mod opening_hours;
 mod parking_lot;
+mod permit;
+mod pricing;
+mod queue;
 mod random_generator;
+mod report;
+mod scenario;
+mod schedule;
 mod simulator;
+mod special_event;
+mod tariff;
 mod triangular_distribution;
+mod vehicle;
+mod warm_up;
 
 pub const MAX_CAPACITY: usize = 512;
 
@@ -64,13 +92,14 @@ impl<I: Iterator<Item = usize>> Iterator for IterUntilDone<'_, I> {
 
 fn par_simulate_inner(
     cli: &cli::Cli,
+    scenario: &Scenario,
     smallest: &AtomicUsize,
     done: &AtomicBool,
     capacity: usize,
     parallel: bool,
 ) {
-    let average = simulate_capacity(capacity, cli, parallel);
-    if average <= cli.threshold {
+    let evaluation = simulate_capacity(capacity, cli, scenario, parallel);
+    if evaluation.acceptable(cli) {
         // Make the iterator stop producing new values
         done.store(true, Ordering::Relaxed);
         // Update the smallest lot size
@@ -78,117 +107,327 @@ fn par_simulate_inner(
     }
 }
 
-fn simulate(cli: &cli::Cli, inner_parallel: bool) -> usize {
+fn simulate(cli: &cli::Cli, scenario: &Scenario, inner_parallel: bool) -> usize {
     let smallest = AtomicUsize::new(usize::MAX);
     let done = AtomicBool::new(false);
     let iter = IterUntilDone::new(1.., &done);
 
     iter.par_bridge().for_each(|capacity| {
-        par_simulate_inner(cli, &smallest, &done, capacity, inner_parallel);
+        par_simulate_inner(cli, scenario, &smallest, &done, capacity, inner_parallel);
     });
 
     smallest.load(Ordering::Relaxed)
 }
 
-fn simulate_capacity(capacity: usize, cli: &cli::Cli, parallel: bool) -> f32 {
-    let inner_loop = |rng: &mut rand::rngs::ThreadRng, i: u32| {
-        let (start, end, cars_left) = if cli.event_based {
-            let mut sim = EventSimulator::new(
-                capacity,
-                cli.max_stay,
-                cli.duration,
-                cli.cars_per_hour,
-                cli.skew,
-                rng,
-            );
+/// The results of simulating a single capacity, averaged over every run.
+struct Evaluation {
+    /// The average number of cars left in the queue at the end of a run.
+    cars_left: f32,
+    /// The average number of cars that gave up on parking during a run.
+    cars_lost: f32,
+    /// The fraction of permit holders that found no reserved spot, over every run.
+    permit_refusal_rate: f32,
+    /// The expected total cost of a run, including the cost of the spots.
+    cost: f64,
+    /// The half-width of the 95% confidence interval of [`Self::cost`].
+    cost_margin: f64,
+}
+
+impl Evaluation {
+    /// Whether the capacity is large enough to be considered acceptable.
+    fn acceptable(&self, cli: &cli::Cli) -> bool {
+        self.cars_left <= cli.threshold && cli.max_lost.is_none_or(|max| self.cars_lost <= max)
+    }
+}
+
+/// The results of a single simulation run, or the sum of the results of several runs.
+#[derive(Clone, Copy, Default)]
+struct RunResult {
+    cars_left: usize,
+    cars_lost: usize,
+    permits_refused: usize,
+    permit_arrivals: usize,
+    /// The cost of the queueing and lost customers, not including the cost of the spots.
+    penalty: f64,
+    /// The sum of the squared penalties, used to estimate their variance.
+    penalty_squared: f64,
+}
+
+impl RunResult {
+    fn new<S: Simulator>(sim: &S, cli: &cli::Cli) -> Self {
+        let (permits_refused, permit_arrivals) = sim.permit_refusals();
+        let penalty =
+            cli.wait_cost * sim.wait_time() as f64 + cli.lost_cost * sim.cars_lost() as f64;
+        Self {
+            cars_left: sim.cars_left(),
+            cars_lost: sim.cars_lost(),
+            permits_refused,
+            permit_arrivals,
+            penalty,
+            penalty_squared: penalty * penalty,
+        }
+    }
+
+    fn add(self, other: Self) -> Self {
+        Self {
+            cars_left: self.cars_left + other.cars_left,
+            cars_lost: self.cars_lost + other.cars_lost,
+            permits_refused: self.permits_refused + other.permits_refused,
+            permit_arrivals: self.permit_arrivals + other.permit_arrivals,
+            penalty: self.penalty + other.penalty,
+            penalty_squared: self.penalty_squared + other.penalty_squared,
+        }
+    }
+}
+
+/// Runs the standard simulator once on a given lot, returning the start and end times of the run
+/// alongside its results.
+fn run_standard<P: ParkingLot, R: rand::Rng>(
+    lot: P,
+    cli: &cli::Cli,
+    scenario: &Scenario,
+    rng: &mut R,
+) -> (Instant, Instant, RunResult) {
+    let mut sim = StandardSimulator::new(
+        lot,
+        cli.max_stay,
+        cli.duration,
+        &scenario.classes,
+        cli.continuous,
+        Schedule::new(scenario.capacity_changes.clone()),
+    );
+    let start = Instant::now();
+    sim.simulate(rng);
+    let end = Instant::now();
+    (start, end, RunResult::new(&sim, cli))
+}
+
+fn simulate_capacity(
+    capacity: usize,
+    cli: &cli::Cli,
+    scenario: &Scenario,
+    parallel: bool,
+) -> Evaluation {
+    let inner_loop = |i: u32| {
+        let rng = &mut random_generator::run_rng(cli.seed, capacity, i);
+        let (start, end, result) = if cli.event_based {
+            let mut sim = EventSimulator::new(capacity, scenario, rng);
             let start = Instant::now();
             sim.simulate(rng);
             let end = Instant::now();
-            (start, end, sim.cars_left())
+            (start, end, RunResult::new(&sim, cli))
         } else if cli.continuous_heap {
             let mut sim = ContinuousHeapSimulator::new(
                 capacity,
-                cli.max_stay,
                 cli.duration,
-                cli.cars_per_hour,
-                cli.skew,
+                &scenario.classes,
+                Schedule::new(scenario.capacity_changes.clone()),
             );
             let start = Instant::now();
             sim.simulate(rng);
             let end = Instant::now();
-            (start, end, sim.cars_left())
+            (start, end, RunResult::new(&sim, cli))
+        } else if scenario.pools.len() > 1 {
+            run_standard(scenario.lot(capacity), cli, scenario, rng)
         } else {
-            let mut sim = StandardSimulator::new(
+            run_standard(
                 VecParkingLot::new(capacity),
                 // ArrayParkingLot::new(capacity),
-                cli.max_stay,
-                cli.duration,
-                cli.cars_per_hour,
-                cli.continuous,
-                cli.skew,
-            );
-            let start = Instant::now();
-            sim.simulate(rng);
-            let end = Instant::now();
-            (start, end, sim.cars_left())
+                cli,
+                scenario,
+                rng,
+            )
         };
 
         let runtime = end - start;
 
         if cli.verbose {
             eprintln!(
-                "Capacity {capacity}, simulation run {i} ({} ms): Queue length at the end of simulation run: {cars_left}",
+                "Capacity {capacity}, simulation run {i} ({} ms): Queue length at the end of simulation run: {}",
                 runtime.as_millis(),
+                result.cars_left,
             );
         }
 
-        cars_left
+        result
     };
 
-    let final_size_sum = if parallel {
+    let total = if parallel {
         (1..=cli.runs)
             .into_par_iter()
-            .map_init(rand::rng, inner_loop)
-            .sum::<usize>()
+            .map(inner_loop)
+            .reduce(RunResult::default, RunResult::add)
     } else {
-        let mut rng = rand::rng();
         (1..=cli.runs)
-            .map(|n| inner_loop(&mut rng, n))
-            .sum::<usize>()
+            .map(inner_loop)
+            .fold(RunResult::default(), RunResult::add)
     };
 
-    (final_size_sum as f32) / (cli.runs as f32)
+    let runs = cli.runs as f64;
+    let mean_penalty = total.penalty / runs;
+    // The sample variance of the penalty, which is all of the randomness in the cost
+    let variance = ((total.penalty_squared - runs * mean_penalty * mean_penalty)
+        / (runs - 1.0).max(1.0))
+    .max(0.0);
+    // The searches for gates and permits use an unlimited capacity, which has no cost
+    let cost = cli.spot_cost.map_or(mean_penalty, |spot_cost| {
+        let spots = scenario
+            .pool_sizes(capacity)
+            .into_iter()
+            .fold(0, usize::saturating_add);
+        spot_cost * spots as f64 + mean_penalty
+    });
+
+    Evaluation {
+        cars_left: (total.cars_left as f32) / (cli.runs as f32),
+        cars_lost: (total.cars_lost as f32) / (cli.runs as f32),
+        permit_refusal_rate: total.permits_refused as f32 / total.permit_arrivals.max(1) as f32,
+        cost,
+        cost_margin: 1.96 * (variance / runs).sqrt(),
+    }
 }
 
-fn binary_search_simulate(cli: &cli::Cli) -> usize {
-    // Start by doubling the tested capacity until we reach one that works
-    let mut upper_bound = 1usize;
-    loop {
-        let average = simulate_capacity(upper_bound, cli, true);
-        if average <= cli.threshold {
-            break;
+/// The number of capacities that the binary search simulates at once.
+/// By default, this is the number of threads that the runs of a single capacity leave idle.
+fn search_probes(cli: &cli::Cli) -> usize {
+    cli.probes.map_or_else(
+        || (rayon::current_num_threads() / cli.runs as usize).max(1),
+        |probes| probes as usize,
+    )
+}
+
+/// Simulates several capacities at once, returning whether each one is acceptable.
+fn acceptable_all(cli: &cli::Cli, scenario: &Scenario, capacities: &[usize]) -> Vec<bool> {
+    capacities
+        .par_iter()
+        .map(|&capacity| simulate_capacity(capacity, cli, scenario, true).acceptable(cli))
+        .collect()
+}
+
+/// Finds the largest capacity known not to work (or 0) and the smallest capacity known to work.
+///
+/// This starts from the analytic estimate (without the pools of a fixed size, which are on top of the capacity),
+/// then takes steps that double in size until it has a capacity that works and one that doesn't.
+/// Each round tests the next few steps at once, and the ones past the first step that settles it are ignored.
+/// Steps up never go past [`analytic::capacity_limit`], and if even that doesn't work, there is no answer.
+fn bracket_capacity(cli: &cli::Cli, scenario: &Scenario, probes: usize) -> (usize, usize) {
+    let estimate = Estimate::new(scenario);
+    let fixed = scenario.pool_sizes(0).iter().sum::<usize>();
+    let guess = estimate.spots.saturating_sub(fixed).max(1);
+    // The first step is the standard deviation of the demand for spots
+    let mut step = (estimate.load.sqrt().ceil() as usize).max(1);
+
+    if acceptable_all(cli, scenario, &[guess])[0] {
+        let mut upper_bound = guess;
+        loop {
+            let mut capacities = Vec::with_capacity(probes);
+            let mut next = upper_bound;
+            while capacities.len() < probes {
+                next = next.saturating_sub(step);
+                if next == 0 {
+                    break;
+                }
+                capacities.push(next);
+                step = step.saturating_mul(2);
+            }
+
+            let results = acceptable_all(cli, scenario, &capacities);
+            let failed = results.iter().position(|&acceptable| !acceptable);
+            if let Some(&capacity) = capacities[..failed.unwrap_or(capacities.len())].last() {
+                upper_bound = capacity;
+            }
+            if let Some(i) = failed {
+                return (capacities[i], upper_bound);
+            } else if next == 0 {
+                return (0, upper_bound);
+            }
+        }
+    } else {
+        let limit = analytic::capacity_limit(scenario).max(guess + 1);
+        let mut too_low = guess;
+        loop {
+            let mut capacities = Vec::with_capacity(probes);
+            let mut next = too_low;
+            while capacities.len() < probes && next < limit {
+                next = next.saturating_add(step).min(limit);
+                capacities.push(next);
+                step = step.saturating_mul(2);
+            }
+
+            let results = acceptable_all(cli, scenario, &capacities);
+            let worked = results.iter().position(|&acceptable| acceptable);
+            if let Some(&capacity) = capacities[..worked.unwrap_or(capacities.len())].last() {
+                too_low = capacity;
+            }
+            if let Some(i) = worked {
+                return (too_low, capacities[i]);
+            } else if next == limit {
+                cli::Cli::command()
+                    .error(
+                        ErrorKind::ValueValidation,
+                        format!(
+                            "no capacity up to {limit} spots (enough to hold every car that arrives) meets the targets"
+                        ),
+                    )
+                    .exit()
+            }
         }
-        upper_bound <<= 1;
     }
+}
 
-    let lower_bound = (upper_bound >> 1) + 1;
+/// Searches for the smallest acceptable capacity, assuming that every larger capacity is also acceptable.
+///
+/// Rather than testing one midpoint at a time, each round tests every midpoint that the next few steps
+/// of the binary search could go on to test, then follows the path that the binary search would have taken.
+/// The capacities off that path are wasted, but the search takes far fewer rounds when there are spare threads,
+/// and (given a --seed) finds the same capacity whatever the number of probes.
+fn binary_search_simulate(cli: &cli::Cli, scenario: &Scenario) -> usize {
+    let probes = search_probes(cli);
+    let (too_low, upper_bound) = bracket_capacity(cli, scenario, probes);
 
     // Binary search
-    let mut low = lower_bound;
-    let mut high = upper_bound;
-    let mut mid;
+    let mut low = too_low + 1;
+    // The upper bound is already known to work
+    let mut high = upper_bound - 1;
+    // Testing `depth` levels of the binary search at once takes 2^depth - 1 probes
+    let depth = (probes + 1).ilog2();
 
     while low <= high {
-        mid = usize::midpoint(high, low);
-        // Run the simulation
-        let average = simulate_capacity(mid, cli, true);
-        let too_high = average <= cli.threshold;
-
-        // Try smaller capacities if we overestimated, larger if we underestimated
-        if too_high {
-            high = mid - 1;
-        } else {
-            low = mid + 1;
+        // The midpoints of every range that the binary search could narrow down to
+        let mut capacities = Vec::new();
+        let mut ranges = vec![(low, high)];
+        for _ in 0..depth {
+            ranges = ranges
+                .into_iter()
+                .filter(|&(low, high)| low <= high)
+                .flat_map(|(low, high)| {
+                    let mid = usize::midpoint(high, low);
+                    capacities.push(mid);
+                    [(low, mid - 1), (mid + 1, high)]
+                })
+                .collect();
+        }
+
+        // Run the simulations
+        let results = acceptable_all(cli, scenario, &capacities);
+
+        for _ in 0..depth {
+            if low > high {
+                break;
+            }
+            let mid = usize::midpoint(high, low);
+            let i = capacities
+                .iter()
+                .position(|&capacity| capacity == mid)
+                .expect("every midpoint on the path should have been tested");
+            let too_high = results[i];
+
+            // Try smaller capacities if we overestimated, larger if we underestimated
+            if too_high {
+                high = mid - 1;
+            } else {
+                low = mid + 1;
+            }
         }
     }
 
@@ -196,9 +435,235 @@ fn binary_search_simulate(cli: &cli::Cli) -> usize {
     low
 }
 
-fn faithful_simulate(cli: &cli::Cli) -> usize {
-    let mut rng = rand::rng();
+/// Finds the capacity with the lowest expected total cost,
+/// trading the cost of the spots off against the cost of queueing and losing customers.
+///
+/// Capacities are tested in order (starting from none, since the cost of queueing may be lower than any spot)
+/// until the cost of the spots alone is more than the lowest total cost found so far,
+/// since no larger capacity can be any cheaper.
+fn cost_optimal_capacity(cli: &cli::Cli, scenario: &Scenario, spot_cost: f64) -> usize {
+    let mut best = (usize::MAX, f64::INFINITY);
+
+    for capacity in 0.. {
+        let spots = scenario.pool_sizes(capacity).iter().sum::<usize>();
+        if spot_cost * spots as f64 > best.1 {
+            break;
+        }
+
+        let evaluation = simulate_capacity(capacity, cli, scenario, true);
+        if cli.verbose {
+            eprintln!(
+                "Capacity {capacity}: expected cost {:.2} ± {:.2}",
+                evaluation.cost, evaluation.cost_margin
+            );
+        }
+        if evaluation.cost < best.1 {
+            best = (capacity, evaluation.cost);
+        }
+    }
+
+    best.0
+}
+
+/// Searches for the smallest capacity that works, using whichever search method was selected.
+/// If a cost per spot was given, searches for the cheapest capacity instead.
+fn search_capacity(cli: &cli::Cli, scenario: &Scenario) -> usize {
+    if let Some(spot_cost) = cli.spot_cost {
+        cost_optimal_capacity(cli, scenario, spot_cost)
+    } else if cli.binary_search {
+        binary_search_simulate(cli, scenario)
+    } else {
+        simulate(cli, scenario, !cli.event_based)
+    }
+}
+
+/// Searches for the size of each pool separately, starting from the sizes for a given capacity.
+/// Each pool is searched for in turn while the other pools are kept at their current sizes,
+/// so pools that are only used as a fallback can end up smaller than their fixed share.
+///
+/// Returns the scenario with the sizes of the pools fixed (other than the main pool),
+/// along with the new size of the main pool.
+fn size_pools(cli: &cli::Cli, scenario: &Scenario, capacity: usize) -> (Scenario, usize) {
+    let main_pool = scenario.main_pool();
+    let mut sizes = scenario.pool_sizes(capacity);
+
+    // Pools with a size given by the user are left alone
+    let searched = scenario
+        .pools
+        .iter()
+        .enumerate()
+        .filter(|(_, pool)| !matches!(pool.size, PoolSize::Fixed(_)))
+        .map(|(i, _)| i)
+        .collect::<Vec<_>>();
+
+    let with_sizes = |sizes: &[usize], searched_pool: usize| {
+        let mut scenario = scenario.clone();
+        for &i in &searched {
+            scenario.pools[i].size = if i == searched_pool {
+                PoolSize::Remainder
+            } else {
+                PoolSize::Fixed(sizes[i])
+            };
+        }
+        scenario
+    };
+
+    for &pool in &searched {
+        let scenario = with_sizes(&sizes, pool);
+        let size = search_capacity(cli, &scenario);
+        // The threshold searches start from one spot, but a pool that is only a fallback may not need any
+        sizes[pool] = if cli.spot_cost.is_none()
+            && simulate_capacity(0, cli, &scenario, true).acceptable(cli)
+        {
+            0
+        } else {
+            size
+        };
+    }
+
+    (with_sizes(&sizes, main_pool), sizes[main_pool])
+}
+
+/// The most entrance gates that --search-gates will consider.
+const MAX_ENTRY_GATES: usize = 1000;
+
+/// Finds the smallest number of entrance gates that keeps the queue acceptable,
+/// assuming that the lot never runs out of spots.
+fn search_entry_gates(cli: &cli::Cli, scenario: &Scenario) -> usize {
+    let Some(config) = scenario.entry_gates else {
+        return 0;
+    };
+
+    for count in 1..=MAX_ENTRY_GATES {
+        let scenario = Scenario {
+            entry_gates: Some(GateConfig { count, ..config }),
+            ..scenario.clone()
+        };
+        if simulate_capacity(usize::MAX, cli, &scenario, true).acceptable(cli) {
+            return count;
+        }
+    }
+
+    // Cars that are lost for other reasons (e.g. balking) can make every number of gates unacceptable
+    cli::Cli::command()
+        .error(
+            ErrorKind::ValueValidation,
+            format!("no number of entrance gates up to {MAX_ENTRY_GATES} meets the targets"),
+        )
+        .exit()
+}
+
+/// The most permits per reserved spot that --oversell will consider selling.
+const MAX_PERMITS_PER_SPOT: usize = 100;
+
+/// Finds the largest number of permits that can be sold for the reserved spots,
+/// while keeping the fraction of permit holders finding no reserved spot under the target.
+/// Like the gate search, this assumes that there are always walk-in spots,
+/// since walk-ins can't take reserved spots away from permit holders until they are released.
+fn search_permits_sold(cli: &cli::Cli, scenario: &Scenario, target: f32) -> usize {
+    let Some(permits) = scenario.permits else {
+        return 0;
+    };
+    let Some(holders) = permits.holders else {
+        return 0;
+    };
+
+    let refusal_rate = |sold| {
+        let scenario = Scenario {
+            permits: Some(Permits {
+                holders: Some(PermitHolders { sold, ..holders }),
+                ..permits
+            }),
+            ..scenario.clone()
+        };
+        simulate_capacity(usize::MAX, cli, &scenario, true).permit_refusal_rate
+    };
+
+    // Every extra permit sold can only make things worse for the other holders,
+    // so the largest number that meets the target can be bisected for.
+    // Start from one permit per reserved spot, and bracket the answer between a number that meets the target
+    // and one that doesn't, by doubling the step upwards (never selling more than MAX_PERMITS_PER_SPOT per spot)
+    let Some(PoolSize::Fixed(spots)) = scenario.pools.get(permits.pool).map(|pool| pool.size)
+    else {
+        return 0;
+    };
+    let (mut low, mut high) = if refusal_rate(spots) <= target {
+        let limit = spots.max(1) * MAX_PERMITS_PER_SPOT;
+        let (mut low, mut step) = (spots, spots.max(1));
+        loop {
+            let next = (low + step).min(limit);
+            if next == low {
+                return low;
+            }
+            if refusal_rate(next) > target {
+                break (low, next);
+            }
+            low = next;
+            step *= 2;
+        }
+    } else {
+        // Selling no permits always meets the target
+        (0, spots)
+    };
+    while high - low > 1 {
+        let middle = low + (high - low) / 2;
+        if refusal_rate(middle) <= target {
+            low = middle;
+        } else {
+            high = middle;
+        }
+    }
+    low
+}
+
+/// Searches for the capacity needed if the price never changed,
+/// and prints the results of that lot alongside the lot with dynamic pricing.
+fn compare_static(cli: &cli::Cli, scenario: &Scenario, capacity: usize) {
+    let static_scenario = Scenario {
+        pricing: None,
+        ..scenario.clone()
+    };
+    let static_capacity = search_capacity(cli, &static_scenario);
+
+    let dynamic_stats = report::collect(capacity, cli, scenario);
+    let static_stats = report::collect(static_capacity, cli, &static_scenario);
+    eprintln!(
+        "\nComparison with a static price (averaged over {} runs):",
+        cli.runs
+    );
+    report::print_comparison(
+        cli.runs,
+        &[
+            ("Dynamic", capacity, scenario, &dynamic_stats),
+            ("Static", static_capacity, &static_scenario, &static_stats),
+        ],
+    );
+}
+
+/// Searches for the capacity needed without the admission policy,
+/// and prints the results of that lot alongside the lot with the policy.
+fn compare_admission(cli: &cli::Cli, scenario: &Scenario, capacity: usize) {
+    let open_scenario = Scenario {
+        admission: None,
+        ..scenario.clone()
+    };
+    let open_capacity = search_capacity(cli, &open_scenario);
+
+    let policy_stats = report::collect(capacity, cli, scenario);
+    let open_stats = report::collect(open_capacity, cli, &open_scenario);
+    eprintln!(
+        "\nComparison with admitting cars until the lot is full (averaged over {} runs):",
+        cli.runs
+    );
+    let lots = [
+        ("With policy", capacity, scenario, &policy_stats),
+        ("Without", open_capacity, &open_scenario, &open_stats),
+    ];
+    report::print_comparison(cli.runs, &lots);
+    report::print_class_comparison(cli.runs, &lots);
+}
 
+fn faithful_simulate(cli: &cli::Cli) -> usize {
     for capacity in 1.. {
         let mut final_size_sum = 0;
 
@@ -211,7 +676,7 @@ fn faithful_simulate(cli: &cli::Cli) -> usize {
                 cli.cars_per_hour,
             );
             let start = Instant::now();
-            sim.simulate(&mut rng);
+            sim.simulate(&mut random_generator::run_rng(cli.seed, capacity, i));
             let end = Instant::now();
             let runtime = end - start;
 
@@ -235,10 +700,48 @@ fn faithful_simulate(cli: &cli::Cli) -> usize {
     unreachable!();
 }
 
+/// Prints anything else that was searched for or detected along with the capacity.
+fn print_findings(cli: &cli::Cli, scenario: &Scenario, sizes: &[usize]) {
+    if cli.search_gates
+        && let Some(config) = scenario.entry_gates
+    {
+        eprint!(
+            "\nThe smallest number of entrance gates required: {}",
+            config.count
+        );
+    }
+    if matches!(cli.warm_up, Some(WarmUp::Auto)) {
+        eprint!(
+            "\nThe detected warm-up period: {} seconds",
+            scenario.warm_up
+        );
+    }
+    if cli.oversell.is_some()
+        && let Some(holders) = scenario.permits.and_then(|permits| permits.holders)
+    {
+        eprint!(
+            "\nThe largest number of permits that can be sold: {}",
+            holders.sold
+        );
+    }
+    if scenario.pools.len() > 1 {
+        let pools = scenario
+            .pools
+            .iter()
+            .zip(sizes)
+            .map(|(pool, size)| format!("{} {size}", pool.name))
+            .collect::<Vec<_>>();
+        let kind = scenario.routing.map_or("pool", |_| "lot");
+        eprint!("\nSpots per {kind}: {}", pools.join(", "));
+    }
+    Estimate::new(scenario).print(scenario, sizes.iter().sum());
+}
+
 fn main() {
     let cli = cli::Cli::parse();
     assert!(
-        cli.cars_per_hour > 0.0,
+        cli.cars_per_hour > 0.0
+            || (cli.cars_per_hour == 0.0 && !(cli.classes.is_empty() && cli.events.is_empty())),
         "There must be a positive number of cars per hour."
     );
     assert!(
@@ -248,26 +751,142 @@ fn main() {
 
     let start_time = Instant::now();
 
-    let capacity = if cli.binary_search {
-        binary_search_simulate(&cli)
-    } else if cli.faithful {
+    let mut scenario = Scenario::new(&cli).unwrap_or_else(|message| {
+        cli::Cli::command()
+            .error(ErrorKind::ValueValidation, message)
+            .exit()
+    });
+
+    if matches!(cli.warm_up, Some(WarmUp::Auto)) {
+        scenario.warm_up = warm_up::detect(&cli, &scenario);
+    }
+
+    // The gates have to be found first, since the number of spots required depends on them
+    if cli.search_gates {
+        let count = search_entry_gates(&cli, &scenario);
+        if let Some(config) = &mut scenario.entry_gates {
+            config.count = count;
+        }
+    }
+
+    // Like the gates, the number of permits sold changes the demand for walk-in spots
+    if let Some(target) = cli.oversell {
+        let sold = search_permits_sold(&cli, &scenario, target);
+        if let Some(holders) = scenario.permits.as_mut().and_then(|p| p.holders.as_mut()) {
+            holders.sold = sold;
+        }
+    }
+
+    let mut capacity = if cli.faithful {
         faithful_simulate(&cli)
     } else {
-        simulate(&cli, !cli.event_based)
+        search_capacity(&cli, &scenario)
     };
 
+    if cli.size_pools {
+        (scenario, capacity) = size_pools(&cli, &scenario, capacity);
+    }
+
     let end_time = Instant::now();
     let runtime = end_time - start_time;
 
+    // Pools with a fixed size are on top of the searched capacity
+    let sizes = scenario.pool_sizes(capacity);
+    let total = sizes.iter().sum::<usize>();
+
     // Send the final capacity to stdout and the rest of the text to stderr.
     // Also flushes buffers to make sure that the text appears in the right order
     std::io::stdout().flush().unwrap();
-    eprint!("\nSIMULATION IS COMPLETE!\nThe smallest number of parking spots required: ");
+    if cli.spot_cost.is_some() {
+        eprint!("\nSIMULATION IS COMPLETE!\nThe number of parking spots with the lowest cost: ");
+    } else {
+        eprint!("\nSIMULATION IS COMPLETE!\nThe smallest number of parking spots required: ");
+    }
     std::io::stderr().flush().unwrap();
-    print!("{capacity}");
+    print!("{total}");
     std::io::stdout().flush().unwrap();
+    print_findings(&cli, &scenario, &sizes);
+    if cli.spot_cost.is_some() {
+        // The lowest cost found by the search is biased down by the noise,
+        // so the final capacity gets a fresh estimate
+        let evaluation = simulate_capacity(capacity, &cli, &scenario, true);
+        eprint!(
+            "\nExpected total cost per run: {:.2} ± {:.2} (95% confidence)",
+            evaluation.cost, evaluation.cost_margin
+        );
+    }
     eprintln!(
         "\nTotal execution time: {:.3} seconds",
         runtime.as_secs_f32()
     );
+
+    if cli.report {
+        let stats = report::collect(capacity, &cli, &scenario);
+        report::print(capacity, &cli, &scenario, &stats);
+    }
+
+    if cli.compare_static {
+        compare_static(&cli, &scenario, capacity);
+    }
+
+    if cli.compare_admission {
+        compare_admission(&cli, &scenario, capacity);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Parses the command line arguments (after the program name) and builds their scenario.
+    fn parse(args: &[&str]) -> (cli::Cli, Scenario) {
+        let cli = cli::Cli::parse_from([&["test"], args].concat());
+        let scenario = Scenario::new(&cli).unwrap();
+        (cli, scenario)
+    }
+
+    #[test]
+    fn oversold_permits_meet_the_target() {
+        let (cli, scenario) = parse(&[
+            "10",
+            "-e",
+            "-r",
+            "2",
+            "--seed",
+            "1",
+            "--class",
+            "perm,rate=5,permit",
+            "--permits",
+            "5",
+            "--show-up",
+            "0.9",
+            "--oversell",
+            "0.05",
+        ]);
+        let refusal_rate = |sold| {
+            let mut scenario = scenario.clone();
+            if let Some(holders) = scenario.permits.as_mut().and_then(|p| p.holders.as_mut()) {
+                holders.sold = sold;
+            }
+            simulate_capacity(usize::MAX, &cli, &scenario, false).permit_refusal_rate
+        };
+
+        let sold = search_permits_sold(&cli, &scenario, 0.05);
+        // Every holder gets a spot when there are no more permits than spots
+        assert!(sold >= 5);
+        assert!(refusal_rate(sold) <= 0.05);
+        assert!(refusal_rate(sold + 1) > 0.05);
+    }
+
+    #[test]
+    fn seeded_binary_search_does_not_depend_on_the_probes() {
+        for probes in ["1", "2", "3", "7", "15"] {
+            let (cli, scenario) = parse(&["100", "-e", "-b", "--seed", "7", "--probes", probes]);
+            assert_eq!(
+                binary_search_simulate(&cli, &scenario),
+                414,
+                "with {probes} probes"
+            );
+        }
+    }
 }
@@ -0,0 +1,28 @@
+use clap::ValueEnum;
+
+use crate::parking_lot::PoolSpec;
+
+/// How drivers choose between the lots of a network, trying the next one whenever a lot is full.
+#[derive(Clone, Copy, ValueEnum)]
+pub enum Routing {
+    /// The lots are tried in the order that they are given.
+    Preference,
+    /// The nearest lot to the destination is tried first.
+    Distance,
+    /// The cheapest lot is tried first.
+    Price,
+}
+
+impl Routing {
+    /// Sorts the lots that a vehicle can use into the order that its driver tries them.
+    /// Ties keep the order that the lots are given in.
+    pub fn order(self, lots: &[PoolSpec], allowed: &mut [usize]) {
+        match self {
+            Self::Preference => {}
+            Self::Distance => {
+                allowed.sort_by(|&a, &b| lots[a].distance.total_cmp(&lots[b].distance));
+            }
+            Self::Price => allowed.sort_by(|&a, &b| lots[a].price.total_cmp(&lots[b].price)),
+        }
+    }
+}
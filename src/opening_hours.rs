@@ -0,0 +1,45 @@
+use clap::ValueEnum;
+
+use crate::customer_class::{DAY, within_hours};
+
+/// What happens to the cars that are still parked when the lot closes.
+#[derive(Clone, Copy, ValueEnum)]
+pub enum AtClosing {
+    /// The cars leave at closing time, so stays that would go past it are cut short.
+    Leave,
+    /// The cars stay until they would have left anyway, and are counted as overstays.
+    Overstay,
+}
+
+/// The hours of the day during which the lot accepts cars.
+#[derive(Clone, Copy)]
+pub struct OpeningHours {
+    /// The seconds of the day at which the lot opens and closes.
+    /// The lot may close after midnight (e.g. 18:00 to 02:00).
+    pub hours: (u32, u32),
+    pub at_closing: AtClosing,
+}
+
+impl OpeningHours {
+    /// Whether the lot is open at a given simulation time.
+    #[inline]
+    pub fn is_open(&self, timestamp: u32) -> bool {
+        within_hours(self.hours, timestamp)
+    }
+
+    /// The first closing time after a given simulation time,
+    /// or `None` if the lot is open all day (`0-24`) and so never closes.
+    pub fn next_closing(&self, timestamp: u32) -> Option<u32> {
+        // Hours can only start and end at the same time of day when they span the whole day
+        if self.hours.0 % DAY == self.hours.1 % DAY {
+            return None;
+        }
+        let close = self.hours.1 % DAY;
+        let day = timestamp - timestamp % DAY;
+        if timestamp % DAY < close {
+            Some(day + close)
+        } else {
+            Some(day.saturating_add(DAY + close))
+        }
+    }
+}
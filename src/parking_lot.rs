@@ -1,21 +1,37 @@
-use crate::MAX_CAPACITY;
+use std::str::FromStr;
+
+use crate::{MAX_CAPACITY, layout::SpotMap};
 
 pub type Spot = u32;
 
+/// The number of units of space in a spot.
+/// Vehicles take up a whole number of units, so that e.g. three or four motorcycles can share a spot.
+pub const UNITS_PER_SPOT: usize = 12;
+
 #[allow(dead_code)]
 pub trait ParkingLot {
     fn can_park(&self) -> bool;
     #[must_use]
     fn try_park(&mut self, timestamp: u32) -> bool;
     fn get_occupancy(&self) -> usize;
+    fn capacity(&self) -> usize;
     fn remove_index(&mut self, index: usize) -> Spot;
     fn iter(&self) -> impl Iterator<Item = &Spot>;
+    /// Closes (or reopens, if negative) a number of spots in a pool of the lot.
+    /// Lots that aren't split into pools ignore the pool.
+    /// If the lot has more cars than the new capacity, they stay parked,
+    /// but no more cars can park until enough of them have left.
+    fn close(&mut self, pool: usize, spots: isize);
 }
 
 /// Parking lot implementation that uses a [`Vec`] for its lot.
 pub struct VecParkingLot {
     pub occupancy: Vec<Spot>,
+    /// The number of open spots.
     pub capacity: usize,
+    /// The number of spots, whether they are open or closed.
+    pub size: usize,
+    pub closed: usize,
 }
 
 impl VecParkingLot {
@@ -24,6 +40,8 @@ impl VecParkingLot {
     pub fn new(capacity: usize) -> Self {
         Self {
             capacity,
+            size: capacity,
+            closed: 0,
             occupancy: Vec::with_capacity(capacity),
         }
     }
@@ -32,7 +50,7 @@ impl VecParkingLot {
 impl ParkingLot for VecParkingLot {
     #[inline]
     fn can_park(&self) -> bool {
-        self.occupancy.len() != self.capacity
+        self.occupancy.len() < self.capacity
     }
 
     fn try_park(&mut self, timestamp: u32) -> bool {
@@ -57,6 +75,16 @@ impl ParkingLot for VecParkingLot {
     fn iter(&self) -> impl Iterator<Item = &Spot> {
         self.occupancy.iter()
     }
+
+    #[inline]
+    fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    fn close(&mut self, _pool: usize, spots: isize) {
+        self.closed = self.closed.saturating_add_signed(spots);
+        self.capacity = self.size.saturating_sub(self.closed);
+    }
 }
 
 /// Parking lot implementation that uses a fixed-size array for its lot (i.e. no heap allocation).
@@ -64,15 +92,25 @@ impl ParkingLot for VecParkingLot {
 pub struct ArrayParkingLot {
     pub occupancy: [Spot; MAX_CAPACITY],
     pub length: usize,
+    /// The number of open spots.
     pub capacity: usize,
+    /// The number of spots, whether they are open or closed.
+    pub size: usize,
+    pub closed: usize,
 }
 
 impl ArrayParkingLot {
     #[inline]
     #[allow(dead_code)]
     pub fn new(capacity: usize) -> Self {
+        assert!(
+            capacity <= MAX_CAPACITY,
+            "The capacity can't be more than {MAX_CAPACITY}."
+        );
         Self {
             capacity,
+            size: capacity,
+            closed: 0,
             length: 0,
             occupancy: [0; MAX_CAPACITY],
         }
@@ -82,7 +120,7 @@ impl ArrayParkingLot {
 impl ParkingLot for ArrayParkingLot {
     #[inline]
     fn can_park(&self) -> bool {
-        self.length != self.capacity
+        self.length < self.capacity
     }
 
     fn try_park(&mut self, timestamp: u32) -> bool {
@@ -110,4 +148,300 @@ impl ParkingLot for ArrayParkingLot {
     fn iter(&self) -> impl Iterator<Item = &Spot> {
         self.occupancy[0..self.length].iter()
     }
+
+    #[inline]
+    fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    fn close(&mut self, _pool: usize, spots: isize) {
+        self.closed = self.closed.saturating_add_signed(spots);
+        self.capacity = self.size.saturating_sub(self.closed);
+    }
+}
+
+/// How the size of a spot pool is determined from the capacity being tested.
+#[derive(Clone, Copy)]
+pub enum PoolSize {
+    /// The pool gets whatever capacity is left over after the other pools.
+    Remainder,
+    /// The pool gets a fixed fraction of the capacity.
+    Share(f32),
+    /// The pool always has the same number of spots.
+    Fixed(usize),
+}
+
+/// A pool of spots as specified on the command line.
+///
+/// The format is `NAME[,share=FRACTION|size=SPOTS][,power=KW][,distance=METRES][,price=MULTIPLIER]`.
+/// A pool without a share or size gets whatever capacity is left over after the other pools.
+/// A pool with a power has a charger of that power at each spot.
+/// The distance and price are used when the pools are separate lots in a network (see [`crate::network`]).
+#[derive(Clone)]
+pub struct PoolSpec {
+    pub name: String,
+    pub size: PoolSize,
+    /// The power of the charger at each spot in kW, or 0 if the spots have no chargers.
+    pub power: f32,
+    /// The distance in metres from the pool to the drivers' destination.
+    pub distance: f32,
+    /// The multiplier of the tariff for parking in the pool.
+    pub price: f32,
+}
+
+impl PoolSpec {
+    /// A pool without chargers, at the destination and charging the normal price.
+    pub fn new(name: &str, size: PoolSize) -> Self {
+        Self {
+            name: name.into(),
+            size,
+            power: 0.0,
+            distance: 0.0,
+            price: 1.0,
+        }
+    }
+}
+
+impl FromStr for PoolSpec {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parts = s.split(',');
+        let name = parts.next().unwrap_or_default();
+        if name.is_empty() || name.contains('=') {
+            return Err("the pool must start with a name".into());
+        }
+
+        let mut pool = Self::new(name, PoolSize::Remainder);
+        for part in parts {
+            match part.split_once('=') {
+                Some(("share", value)) => match value.parse::<f32>() {
+                    Ok(share) if (0.0..=1.0).contains(&share) => {
+                        pool.size = PoolSize::Share(share);
+                    }
+                    _ => return Err(format!("invalid share `{value}`")),
+                },
+                Some(("size", value)) => {
+                    pool.size = PoolSize::Fixed(
+                        value
+                            .parse()
+                            .map_err(|e| format!("invalid size `{value}`: {e}"))?,
+                    );
+                }
+                Some(("power", value)) => match value.parse::<f32>() {
+                    Ok(kw) if kw > 0.0 => pool.power = kw,
+                    _ => return Err(format!("invalid power `{value}`")),
+                },
+                Some(("distance", value)) => match value.parse::<f32>() {
+                    Ok(metres) if metres >= 0.0 => pool.distance = metres,
+                    _ => return Err(format!("invalid distance `{value}`")),
+                },
+                Some(("price", value)) => match value.parse::<f32>() {
+                    Ok(multiplier) if multiplier >= 0.0 => pool.price = multiplier,
+                    _ => return Err(format!("invalid price multiplier `{value}`")),
+                },
+                _ => return Err(format!("unknown pool option `{part}`")),
+            }
+        }
+
+        Ok(pool)
+    }
+}
+
+/// A group of interchangeable spots of the same type.
+/// The capacity and occupancy are measured in units of space (see [`UNITS_PER_SPOT`]).
+/// Only the total space is tracked, not which spots are next to each other,
+/// so a vehicle taking up several spots can use space left over across the pool.
+#[derive(Clone, Copy)]
+pub struct SpotPool {
+    /// The space in the open spots.
+    pub capacity: usize,
+    /// The space taken up by parked vehicles.
+    pub occupancy: usize,
+    /// The number of spots, whether they are open or closed.
+    pub size: usize,
+    /// The number of spots that are currently closed.
+    pub closed: usize,
+}
+
+/// A lot made up of pools of typed spots (e.g. regular, compact, accessible).
+/// Cars can only park in certain pools, which they try in order of preference.
+///
+/// Spots are only told apart if the lot has a layout. Otherwise, the spot of a parked car
+/// is just the index of its pool.
+///
+/// As a [`ParkingLot`], every car is a regular car that tries the default pools in order,
+/// and the lot keeps track of the timestamp of each car instead of its spot.
+pub struct PooledLot {
+    pools: Vec<SpotPool>,
+    /// The number of parked vehicles.
+    parked: usize,
+    /// The individual spots, if the lot has a layout.
+    spots: Option<SpotMap>,
+    /// The pools that cars parked as a [`ParkingLot`] try, in order.
+    default_pools: Vec<usize>,
+    /// The timestamp and pool of each car parked as a [`ParkingLot`].
+    cars: Vec<(Spot, usize)>,
+}
+
+impl PooledLot {
+    /// Creates an empty lot with pools of the given sizes,
+    /// where cars without a vehicle type try the default pools in order.
+    pub fn new(capacities: impl IntoIterator<Item = usize>, default_pools: Vec<usize>) -> Self {
+        Self {
+            pools: capacities
+                .into_iter()
+                .map(|size| SpotPool {
+                    capacity: size.saturating_mul(UNITS_PER_SPOT),
+                    occupancy: 0,
+                    size,
+                    closed: 0,
+                })
+                .collect(),
+            parked: 0,
+            spots: None,
+            default_pools,
+            cars: Vec::new(),
+        }
+    }
+
+    /// Keeps track of which individual spots are taken.
+    pub fn with_spots(mut self, spots: SpotMap) -> Self {
+        self.spots = Some(spots);
+        self
+    }
+
+    /// Finds the first pool out of the allowed ones that has room for a vehicle taking up some units of space.
+    #[inline]
+    pub fn find_spot(&self, allowed: &[usize], units: usize) -> Option<usize> {
+        allowed
+            .iter()
+            .copied()
+            .find(|&pool| self.pools[pool].occupancy + units <= self.pools[pool].capacity)
+    }
+
+    /// Parks a vehicle taking up some units of space in a pool, returning its spot.
+    /// This function assumes that the caller has already checked that the pool has space.
+    #[inline]
+    pub fn park<R: rand::Rng>(
+        &mut self,
+        rng: &mut R,
+        pool: usize,
+        units: usize,
+        timestamp: u32,
+    ) -> usize {
+        self.pools[pool].occupancy += units;
+        self.parked += 1;
+        self.spots
+            .as_mut()
+            .map_or(pool, |spots| spots.park(rng, pool, timestamp))
+    }
+
+    /// Frees the spot of a vehicle taking up some units of space.
+    #[inline]
+    pub fn leave(&mut self, spot: usize, units: usize, timestamp: u32) {
+        let pool = self
+            .spots
+            .as_mut()
+            .map_or(spot, |spots| spots.leave(spot, timestamp));
+        self.pools[pool].occupancy -= units;
+        self.parked -= 1;
+    }
+
+    /// The pool that a spot belongs to.
+    #[inline]
+    pub fn pool(&self, spot: usize) -> usize {
+        self.spots.as_ref().map_or(spot, |spots| spots.pool(spot))
+    }
+
+    /// The individual spots, if the lot has a layout.
+    pub fn spots(&self) -> Option<&SpotMap> {
+        self.spots.as_ref()
+    }
+
+    /// Closes (or reopens, if negative) a number of spots in a pool.
+    /// Cars parked in the pool stay parked, even if there are more of them than open spots.
+    pub fn close(&mut self, pool: usize, spots: isize) {
+        let pool = &mut self.pools[pool];
+        pool.closed = pool.closed.saturating_add_signed(spots);
+        pool.capacity = pool
+            .size
+            .saturating_sub(pool.closed)
+            .saturating_mul(UNITS_PER_SPOT);
+    }
+
+    /// The free space in some of the pools, in units of space.
+    pub fn free(&self, pools: &[usize]) -> usize {
+        pools.iter().fold(0, |free, &pool| {
+            let pool = &self.pools[pool];
+            free.saturating_add(pool.capacity.saturating_sub(pool.occupancy))
+        })
+    }
+
+    /// The number of parked vehicles.
+    pub fn occupied(&self) -> usize {
+        self.parked
+    }
+
+    /// The fraction of the space in the lot that is taken.
+    pub fn occupancy(&self) -> f32 {
+        // Summed as floats, since a capacity can be as large as `usize::MAX` when searching for gates
+        let (taken, capacity) = self
+            .pools
+            .iter()
+            .fold((0.0, 0.0), |(taken, capacity), pool| {
+                (
+                    taken + pool.occupancy as f64,
+                    capacity + pool.capacity as f64,
+                )
+            });
+        (taken / f64::max(capacity, 1.0)) as f32
+    }
+}
+
+impl ParkingLot for PooledLot {
+    #[inline]
+    fn can_park(&self) -> bool {
+        self.find_spot(&self.default_pools, UNITS_PER_SPOT)
+            .is_some()
+    }
+
+    fn try_park(&mut self, timestamp: u32) -> bool {
+        let Some(pool) = self.find_spot(&self.default_pools, UNITS_PER_SPOT) else {
+            return false;
+        };
+        self.pools[pool].occupancy += UNITS_PER_SPOT;
+        self.parked += 1;
+        self.cars.push((timestamp, pool));
+        true
+    }
+
+    #[inline]
+    fn remove_index(&mut self, index: usize) -> Spot {
+        let (timestamp, pool) = self.cars.swap_remove(index);
+        self.pools[pool].occupancy -= UNITS_PER_SPOT;
+        self.parked -= 1;
+        timestamp
+    }
+
+    #[inline]
+    fn get_occupancy(&self) -> usize {
+        self.parked
+    }
+
+    #[inline]
+    fn iter(&self) -> impl Iterator<Item = &Spot> {
+        self.cars.iter().map(|(timestamp, _)| timestamp)
+    }
+
+    fn capacity(&self) -> usize {
+        self.pools
+            .iter()
+            .map(|pool| pool.capacity / UNITS_PER_SPOT)
+            .sum()
+    }
+
+    fn close(&mut self, pool: usize, spots: isize) {
+        Self::close(self, pool, spots);
+    }
 }
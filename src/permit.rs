@@ -0,0 +1,36 @@
+use crate::customer_class::DAY;
+
+/// Spots that are reserved for permit holders (or pre-booked reservations).
+#[derive(Clone, Copy)]
+pub struct Permits {
+    /// The pool of reserved spots.
+    pub pool: usize,
+    /// The second of the day from which the reserved spots are held for permit holders.
+    pub hold_from: u32,
+    /// How long after [`Self::hold_from`] any free reserved spots are released to walk-ins
+    /// for the rest of the day. If not set, walk-ins can never use reserved spots.
+    pub release_after: Option<u32>,
+    /// The individual permit holders, if their arrivals are modelled per holder
+    /// instead of with the arrival rates of the permit classes.
+    pub holders: Option<PermitHolders>,
+}
+
+/// A number of sold permits whose holders each decide whether to show up every day.
+#[derive(Clone, Copy)]
+pub struct PermitHolders {
+    /// The number of permits sold.
+    pub sold: usize,
+    /// The probability that a permit holder shows up on a given day.
+    pub show_up: f32,
+}
+
+impl Permits {
+    /// Whether walk-ins may park in free reserved spots at a given simulation time.
+    pub fn released(&self, timestamp: u32) -> bool {
+        let Some(grace) = self.release_after else {
+            return false;
+        };
+        let since_hold = (timestamp % DAY + DAY - self.hold_from) % DAY;
+        since_hold >= grace
+    }
+}
@@ -0,0 +1,77 @@
+use std::str::FromStr;
+
+/// A pricing policy that raises the price of the tariff as the lot fills up.
+///
+/// The format is `OCCUPANCY=MULTIPLIER[,OCCUPANCY=MULTIPLIER...]`,
+/// where the tariff is multiplied by the multiplier of the highest occupancy (as a fraction) that has been reached.
+/// For example, `0.9=1.5,0.97=2` charges 1.5 times the normal price once the lot is 90% full,
+/// and twice the normal price once it is 97% full.
+#[derive(Clone)]
+pub struct SurgePricing {
+    /// The occupancy thresholds and their multipliers, sorted by occupancy.
+    steps: Vec<(f32, f32)>,
+}
+
+impl SurgePricing {
+    /// The price multiplier at a given occupancy, as a fraction of the capacity.
+    pub fn multiplier(&self, occupancy: f32) -> f32 {
+        self.steps
+            .iter()
+            .rev()
+            .find(|&&(threshold, _)| occupancy >= threshold)
+            .map_or(1.0, |&(_, multiplier)| multiplier)
+    }
+}
+
+impl FromStr for SurgePricing {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut steps = s
+            .split(',')
+            .map(|part| {
+                let (occupancy, multiplier) = part
+                    .split_once('=')
+                    .ok_or_else(|| format!("expected OCCUPANCY=MULTIPLIER, found `{part}`"))?;
+                let occupancy = match occupancy.parse::<f32>() {
+                    Ok(occupancy) if (0.0..=1.0).contains(&occupancy) => occupancy,
+                    _ => return Err(format!("invalid occupancy `{occupancy}`")),
+                };
+                let multiplier = match multiplier.parse::<f32>() {
+                    Ok(multiplier) if multiplier > 0.0 => multiplier,
+                    _ => return Err(format!("invalid multiplier `{multiplier}`")),
+                };
+                Ok((occupancy, multiplier))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        steps.sort_by(|a, b| a.0.total_cmp(&b.0));
+        Ok(Self { steps })
+    }
+}
+
+/// How drivers respond to the current price.
+#[derive(Clone)]
+pub struct Pricing {
+    pub surge: SurgePricing,
+    /// The price elasticity of demand. An arriving driver who sees the price multiplied by `m`
+    /// still comes in with a probability of `m^-elasticity`.
+    pub arrival_elasticity: f32,
+    /// The price elasticity of the stay. Drivers who pay `m` times the normal price
+    /// stay `m^-elasticity` times as long.
+    pub stay_elasticity: f32,
+}
+
+impl Pricing {
+    /// Whether a driver who sees a given multiplier decides to come in anyway.
+    pub fn accepts<T: rand::Rng>(&self, rng: &mut T, multiplier: f32) -> bool {
+        multiplier <= 1.0 || rng.random::<f32>() < multiplier.powf(-self.arrival_elasticity)
+    }
+
+    /// Scales a stay by the driver's response to a given multiplier.
+    pub fn scale_stay(&self, stay: u32, multiplier: f32) -> u32 {
+        if self.stay_elasticity == 0.0 {
+            return stay;
+        }
+        ((stay as f32 * multiplier.powf(-self.stay_elasticity)) as u32).max(1)
+    }
+}
@@ -0,0 +1,163 @@
+use std::{cmp::Reverse, collections::BinaryHeap, collections::VecDeque};
+
+use clap::ValueEnum;
+
+use crate::random_generator;
+
+/// A car waiting in the queue to enter the lot.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct QueuedCar {
+    pub arrival_time: u32,
+    pub class: usize,
+    pub vehicle: usize,
+    /// The time at which the driver runs out of patience and leaves the queue.
+    pub deadline: u32,
+    /// The time at which the special event that the driver is going to ends,
+    /// after which they no longer want to park (`u32::MAX` for drivers not going to one).
+    pub leave_by: u32,
+}
+
+/// How drivers decide whether to join the queue when they arrive.
+#[derive(Clone, Copy)]
+pub struct Balking {
+    /// The probability of balking for each car that is already in the queue.
+    pub per_queued: f32,
+    /// The additional probability of balking when the lot is full.
+    pub when_full: f32,
+}
+
+impl Balking {
+    /// Randomly determines whether an arriving driver refuses to join the queue.
+    pub fn balks<T: rand::Rng>(self, rng: &mut T, queue_length: usize, lot_full: bool) -> bool {
+        let full = if lot_full { self.when_full } else { 0.0 };
+        let probability = full + self.per_queued * queue_length as f32;
+        probability > 0.0 && random_generator::event_occurred(rng, probability)
+    }
+}
+
+/// Drivers who find the lot full circle the block and try again later, instead of queueing at the entrance.
+#[derive(Clone, Copy)]
+pub struct Cruising {
+    /// The mean number of seconds that a driver spends circling before trying again.
+    pub mean_delay: f32,
+    /// The number of failed retries after which a driver gives up, if they ever do.
+    pub max_retries: Option<u32>,
+    /// The average speed of a cruising car in km/h.
+    pub speed: f32,
+}
+
+impl Cruising {
+    /// Generates the time until a cruising driver tries again.
+    pub fn delay<T: rand::Rng>(self, rng: &mut T) -> u32 {
+        random_generator::exponential(rng, self.mean_delay)
+            .ceil()
+            .max(1.0) as u32
+    }
+
+    /// The distance in km travelled while cruising for a number of seconds.
+    pub fn distance(self, seconds: u64) -> f64 {
+        seconds as f64 / 3600.0 * f64::from(self.speed)
+    }
+}
+
+/// What happens to cars that arrive when the entrance lane is full.
+#[derive(Clone, Copy, ValueEnum)]
+pub enum Overflow {
+    /// The cars are turned away.
+    TurnAway,
+    /// The cars keep queueing on the street.
+    Spill,
+}
+
+/// The order in which queued cars get to park, out of the cars that have a spot available to them.
+#[derive(Clone, Copy, ValueEnum)]
+pub enum QueueDiscipline {
+    /// First come, first served.
+    Fifo,
+    /// The car of the class with the highest priority goes first,
+    /// and cars of the same priority are served in the order they arrived.
+    Priority,
+    /// Last come, first served.
+    Lifo,
+    /// A random car goes next, like drivers circling the lot and taking whatever spot they find.
+    Random,
+}
+
+/// The queue of cars waiting to enter the lot.
+/// Unlike the plain counters used by the other simulators,
+/// this keeps track of each car so that drivers can give up on waiting.
+#[derive(Default)]
+pub struct EntranceQueue {
+    cars: VecDeque<QueuedCar>,
+    /// The deadlines of every car that has one.
+    /// Cars that have already left the queue are only removed once their deadline is reached.
+    deadlines: BinaryHeap<Reverse<u32>>,
+}
+
+impl EntranceQueue {
+    /// Generates a deadline for a driver, given the mean patience of drivers.
+    pub fn deadline<T: rand::Rng>(rng: &mut T, timestamp: u32, patience: Option<f32>) -> u32 {
+        patience.map_or(u32::MAX, |mean| {
+            let patience = random_generator::exponential(rng, mean).ceil().max(1.0);
+            timestamp.saturating_add(patience as u32)
+        })
+    }
+
+    pub fn push(&mut self, car: QueuedCar) {
+        if car.deadline != u32::MAX {
+            self.deadlines.push(Reverse(car.deadline));
+        }
+        self.cars.push_back(car);
+    }
+
+    /// Removes the car at a given position in the queue.
+    pub fn remove(&mut self, index: usize) -> Option<QueuedCar> {
+        if index == 0 {
+            self.cars.pop_front()
+        } else {
+            self.cars.remove(index)
+        }
+    }
+
+    /// The car at the front of the queue.
+    pub fn front(&self) -> Option<&QueuedCar> {
+        self.cars.front()
+    }
+
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.cars.len()
+    }
+
+    pub fn iter(&self) -> impl DoubleEndedIterator<Item = &QueuedCar> + ExactSizeIterator {
+        self.cars.iter()
+    }
+
+    /// The earliest time at which a driver might run out of patience.
+    pub fn next_deadline(&self) -> Option<u32> {
+        self.deadlines.peek().map(|r| r.0)
+    }
+
+    /// Removes every car whose driver has run out of patience by the given timestamp,
+    /// calling `on_leave` for each of them.
+    pub fn renege(&mut self, timestamp: u32, mut on_leave: impl FnMut(&QueuedCar)) {
+        let mut expired = false;
+        while let Some(&Reverse(deadline)) = self.deadlines.peek() {
+            if deadline > timestamp {
+                break;
+            }
+            self.deadlines.pop();
+            expired = true;
+        }
+
+        if expired {
+            self.cars.retain(|car| {
+                let leaves = car.deadline <= timestamp;
+                if leaves {
+                    on_leave(car);
+                }
+                !leaves
+            });
+        }
+    }
+}
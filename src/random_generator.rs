@@ -1,4 +1,34 @@
+use rand::{SeedableRng, rngs::StdRng};
+
 #[inline]
 pub fn event_occurred<T: rand::Rng>(random: &mut T, probability: f32) -> bool {
     random.random::<f32>() < probability
 }
+
+/// Samples a value from an exponential distribution with a given mean.
+#[inline]
+pub fn exponential<T: rand::Rng>(random: &mut T, mean: f32) -> f32 {
+    -mean * (1.0 - random.random::<f32>()).ln()
+}
+
+/// Samples a value from a Poisson distribution with a given mean,
+/// by counting the events of a Poisson process with a rate of 1 that happen before the mean.
+pub fn poisson<T: rand::Rng>(random: &mut T, mean: f64) -> usize {
+    let mut count = 0;
+    let mut time = -(1.0 - random.random::<f64>()).ln();
+    while time < mean {
+        count += 1;
+        time -= (1.0 - random.random::<f64>()).ln();
+    }
+    count
+}
+
+/// Creates the random number generator for one run at a given capacity.
+/// With a seed, the numbers only depend on the seed, the capacity and the run,
+/// so the results don't depend on the order that capacities and runs are simulated in.
+pub fn run_rng(seed: Option<u64>, capacity: usize, run: u32) -> StdRng {
+    match seed {
+        Some(seed) => StdRng::seed_from_u64(seed ^ ((capacity as u64) << 32 | u64::from(run))),
+        None => StdRng::from_rng(&mut rand::rng()),
+    }
+}
@@ -0,0 +1,749 @@
+use rayon::prelude::*;
+
+use crate::{
+    admission::Admission,
+    cli::Cli,
+    customer_class::DAY,
+    event_simulator::EventSimulator,
+    layout::Layout,
+    opening_hours::{AtClosing, OpeningHours},
+    permit::Permits,
+    queue::{Cruising, Overflow, QueueDiscipline},
+    random_generator,
+    scenario::Scenario,
+    simulator::Simulator,
+};
+
+/// Statistics for a single customer class over a simulation run.
+#[derive(Clone, Default)]
+pub struct ClassStats {
+    /// The number of cars that arrived at the lot.
+    pub arrived: u64,
+    /// The number of cars whose drivers decided not to join the queue.
+    pub balked: u64,
+    /// The number of cars whose drivers gave up on waiting in the queue.
+    pub reneged: u64,
+    /// The number of cars that were turned away because the entrance lane was full.
+    pub turned_away: u64,
+    /// The number of cars that made it into the lot.
+    pub parked: u64,
+    /// The total number of seconds that cars spent in the queue,
+    /// including cars that were still queued at the end of the simulation.
+    pub wait_time: u64,
+    /// The total number of seconds that spots were occupied by this class,
+    /// up to the end of the simulation.
+    pub spot_time: u64,
+    /// The number of cars still in the queue at the end of the simulation.
+    pub queued: u64,
+    /// The total price paid by the cars that parked for their time in their spots,
+    /// up to the end of the simulation.
+    pub revenue: f64,
+    /// The number of drivers that decided not to come in because of the current price.
+    pub priced_out: u64,
+    /// The number of cars that were still in the queue when the lot closed.
+    pub closed_out: u64,
+    /// The number of drivers that gave up after cruising for a spot.
+    pub gave_up: u64,
+    /// The number of drivers going to a special event that hadn't parked by the time that it ended.
+    pub missed_event: u64,
+    /// The longest time that a car spent in the queue.
+    /// When runs are added together, this is the longest wait in any run.
+    pub longest_wait: u64,
+    /// The number of cars that arrived while there was space for them,
+    /// but that the admission policy didn't let in.
+    pub held_back: u64,
+}
+
+impl ClassStats {
+    /// The number of cars that never made it into the queue or gave up on waiting.
+    pub fn lost(&self) -> u64 {
+        self.balked
+            + self.reneged
+            + self.turned_away
+            + self.priced_out
+            + self.gave_up
+            + self.missed_event
+    }
+
+    fn add(&mut self, other: &Self) {
+        self.arrived += other.arrived;
+        self.balked += other.balked;
+        self.reneged += other.reneged;
+        self.turned_away += other.turned_away;
+        self.parked += other.parked;
+        self.wait_time += other.wait_time;
+        self.spot_time += other.spot_time;
+        self.queued += other.queued;
+        self.revenue += other.revenue;
+        self.priced_out += other.priced_out;
+        self.closed_out += other.closed_out;
+        self.gave_up += other.gave_up;
+        self.missed_event += other.missed_event;
+        self.longest_wait = self.longest_wait.max(other.longest_wait);
+        self.held_back += other.held_back;
+    }
+
+    /// The average time that a driver spent in the queue.
+    /// Drivers that balked or were priced out never waited, so they aren't included in the average.
+    fn average_wait(&self) -> f64 {
+        self.wait_time as f64 / (self.arrived - self.balked - self.priced_out).max(1) as f64
+    }
+
+    /// The revenue that would have come from the cars that never parked,
+    /// assuming that they would have paid as much as the cars that did on average.
+    fn lost_revenue(&self) -> f64 {
+        self.revenue / self.parked.max(1) as f64
+            * (self.lost() + self.queued + self.closed_out) as f64
+    }
+}
+
+/// Statistics for a single pool of spots over a simulation run.
+#[derive(Clone, Default)]
+pub struct PoolStats {
+    /// The number of cars that parked in this pool.
+    pub parked: u64,
+    /// The total number of seconds that spots in this pool were occupied,
+    /// up to the end of the simulation.
+    pub spot_time: u64,
+    /// The total number of seconds that cars preferring this pool spent in the queue.
+    pub wait_time: u64,
+    /// The number of cars preferring this pool that were still in the queue
+    /// at the end of the simulation.
+    pub queued: u64,
+}
+
+impl PoolStats {
+    fn add(&mut self, other: &Self) {
+        self.parked += other.parked;
+        self.spot_time += other.spot_time;
+        self.wait_time += other.wait_time;
+        self.queued += other.queued;
+    }
+}
+
+/// Statistics collected by the event-based simulator over a simulation run.
+#[derive(Clone, Default)]
+pub struct RunStats {
+    pub classes: Vec<ClassStats>,
+    pub pools: Vec<PoolStats>,
+    /// The longest that the queue got during the run.
+    /// When runs are added together, this is the longest queue in any of them.
+    pub longest_queue: u64,
+    /// The sum of the longest queue of each run, used to average them over the runs.
+    pub longest_queue_total: u64,
+    /// The number of seconds that the queue spent at each length.
+    pub queue_time: Vec<u64>,
+    /// The number of seconds during which the queue was longer than the entrance lane.
+    pub spillback_time: u64,
+    /// The number of seconds during which the "LOT FULL" sign was on.
+    pub sign_time: u64,
+    /// The number of cars that made it through the exit gates.
+    pub exited: u64,
+    /// The total number of seconds between cars departing and making it through the exit gates.
+    pub exit_time: u64,
+    /// The longest that the exit queue got during the run.
+    /// When runs are added together, this is the sum of each run's longest exit queue.
+    pub longest_exit_queue: u64,
+    /// The number of EVs that parked at a charger.
+    pub charged: u64,
+    /// The number of EVs that parked in a spot without a charger.
+    pub uncharged: u64,
+    /// The total number of seconds that EVs spent charging.
+    pub charge_time: u64,
+    /// The total energy delivered to EVs, in Wh.
+    pub energy: u64,
+    /// The number of EVs that moved off their charger to a regular spot.
+    pub moved: u64,
+    /// The number of EVs that stayed at their charger because there was no spot to move to.
+    pub stayed_plugged_in: u64,
+    /// The number of permit holders that arrived to find every reserved spot taken.
+    pub no_permit_spot: u64,
+    /// The number of walk-ins that parked in a released reserved spot.
+    pub released_parked: u64,
+    /// The number of cars that were forced to leave when the lot closed.
+    pub forced_departures: u64,
+    /// The number of cars that were still parked when the lot closed, summed over every closing.
+    pub overstays: u64,
+    /// The number of drivers that found the lot full and started cruising for a spot.
+    pub cruised: u64,
+    /// The number of times that cruising drivers tried the lot again.
+    pub retries: u64,
+    /// The total number of seconds that drivers spent cruising.
+    pub cruise_time: u64,
+    /// The number of cars that walked from their spot to the entrance, if the lot has a layout.
+    pub walked: u64,
+    /// The total distance in metres that those cars walked.
+    pub walking_distance: f64,
+    /// The number of seconds that each spot was taken for, if the lot has a layout.
+    pub spot_busy: Vec<u64>,
+}
+
+impl RunStats {
+    pub fn new(classes: usize, pools: usize) -> Self {
+        Self {
+            classes: vec![ClassStats::default(); classes],
+            pools: vec![PoolStats::default(); pools],
+            ..Default::default()
+        }
+    }
+
+    /// Records the length of the queue, as a candidate for the longest queue of the run.
+    pub fn record_queue(&mut self, length: usize) {
+        self.longest_queue = self.longest_queue.max(length as u64);
+        // Within a single run, the sum of the longest queues is just the longest queue
+        self.longest_queue_total = self.longest_queue;
+    }
+
+    /// Records the queue having a given length for some number of seconds.
+    pub fn record_queue_time(&mut self, length: usize, seconds: u64) {
+        if self.queue_time.len() <= length {
+            self.queue_time.resize(length + 1, 0);
+        }
+        self.queue_time[length] += seconds;
+    }
+
+    /// The shortest entrance lane (in cars) that would have held the queue for a given percentage of the time.
+    pub fn queue_storage_needed(&self, percentile: f32) -> usize {
+        let total = self.queue_time.iter().sum::<u64>();
+        let target = f64::from(percentile) / 100.0 * total as f64;
+        let mut covered = 0;
+        for (length, &time) in self.queue_time.iter().enumerate() {
+            covered += time;
+            if covered as f64 >= target {
+                return length;
+            }
+        }
+        self.queue_time.len().saturating_sub(1)
+    }
+
+    fn add(&mut self, other: &Self) {
+        for (class, other) in self.classes.iter_mut().zip(&other.classes) {
+            class.add(other);
+        }
+        for (pool, other) in self.pools.iter_mut().zip(&other.pools) {
+            pool.add(other);
+        }
+        self.longest_queue = self.longest_queue.max(other.longest_queue);
+        self.longest_queue_total += other.longest_queue_total;
+        if self.queue_time.len() < other.queue_time.len() {
+            self.queue_time.resize(other.queue_time.len(), 0);
+        }
+        for (time, other) in self.queue_time.iter_mut().zip(&other.queue_time) {
+            *time += other;
+        }
+        self.spillback_time += other.spillback_time;
+        self.sign_time += other.sign_time;
+        self.exited += other.exited;
+        self.exit_time += other.exit_time;
+        self.longest_exit_queue += other.longest_exit_queue;
+        self.charged += other.charged;
+        self.uncharged += other.uncharged;
+        self.charge_time += other.charge_time;
+        self.energy += other.energy;
+        self.moved += other.moved;
+        self.stayed_plugged_in += other.stayed_plugged_in;
+        self.no_permit_spot += other.no_permit_spot;
+        self.released_parked += other.released_parked;
+        self.forced_departures += other.forced_departures;
+        self.overstays += other.overstays;
+        self.cruised += other.cruised;
+        self.retries += other.retries;
+        self.cruise_time += other.cruise_time;
+        self.walked += other.walked;
+        self.walking_distance += other.walking_distance;
+        if self.spot_busy.len() < other.spot_busy.len() {
+            self.spot_busy.resize(other.spot_busy.len(), 0);
+        }
+        for (spot, other) in self.spot_busy.iter_mut().zip(&other.spot_busy) {
+            *spot += other;
+        }
+    }
+}
+
+/// Runs the event-based simulator for a given capacity and sums up the statistics of every run.
+pub fn collect(capacity: usize, cli: &Cli, scenario: &Scenario) -> RunStats {
+    (1..=cli.runs)
+        .into_par_iter()
+        .map(|run| {
+            let rng = &mut random_generator::run_rng(cli.seed, capacity, run);
+            let mut sim = EventSimulator::new(capacity, scenario, rng);
+            sim.simulate(rng);
+            sim.stats()
+        })
+        .reduce(
+            || RunStats::new(scenario.classes.len(), scenario.pools.len()),
+            |mut total, run| {
+                total.add(&run);
+                total
+            },
+        )
+}
+
+/// Prints a per-class breakdown of the statistics, averaged over every run.
+pub fn print(capacity: usize, cli: &Cli, scenario: &Scenario, total: &RunStats) {
+    let runs = cli.runs as f64;
+    let spot_time_sum = total
+        .classes
+        .iter()
+        .map(|c| c.spot_time)
+        .sum::<u64>()
+        .max(1);
+
+    let sizes = scenario.pool_sizes(capacity);
+    eprintln!(
+        "\nBreakdown for {} spots (averaged over {runs} runs):",
+        sizes.iter().sum::<usize>()
+    );
+    eprintln!(
+        "{:<16}{:>10}{:>10}{:>10}{:>14}{:>10}{:>20}",
+        "Class", "Arrivals", "Parked", "Lost", "Avg wait (s)", "Queued", "Spot-hours"
+    );
+    for (class, stats) in scenario.classes.iter().zip(&total.classes) {
+        let average_wait = stats.average_wait();
+        let share = stats.spot_time as f64 / spot_time_sum as f64 * 100.0;
+        eprintln!(
+            "{:<16}{:>10.1}{:>10.1}{:>10.1}{:>14.1}{:>10.1}{:>12.1} ({share:>5.1}%)",
+            class.name,
+            stats.arrived as f64 / runs,
+            stats.parked as f64 / runs,
+            stats.lost() as f64 / runs,
+            average_wait,
+            stats.queued as f64 / runs,
+            stats.spot_time as f64 / runs / 3600.0,
+        );
+    }
+
+    if scenario.pools.len() > 1 {
+        print_pools(capacity, runs, scenario, total);
+    }
+
+    print_priorities(runs, scenario, total);
+
+    print_lost(runs, total);
+
+    if let Some(cruising) = scenario.cruising {
+        print_cruising(runs, cruising, total);
+    }
+
+    if let Some(admission) = &scenario.admission {
+        print_admission(runs, scenario, admission, total);
+    }
+
+    eprintln!(
+        "Longest queue per run: {:.1} cars on average, {} at worst",
+        total.longest_queue_total as f64 / runs,
+        total.longest_queue
+    );
+    eprintln!(
+        "Entrance lane storage needed to hold the queue {}% of the time: {} cars",
+        cli.storage_percentile,
+        total.queue_storage_needed(cli.storage_percentile)
+    );
+    if let Some(storage) = scenario.queue_storage
+        && matches!(scenario.overflow, Overflow::Spill)
+    {
+        eprintln!(
+            "Time spent with the queue past the entrance lane ({storage} cars) per run: {:.1} minutes",
+            total.spillback_time as f64 / runs / 60.0
+        );
+    }
+
+    if scenario.exit_gates.is_some() {
+        eprintln!(
+            "Average time to exit: {:.1} seconds (longest exit queue per run: {:.1} cars)",
+            total.exit_time as f64 / total.exited.max(1) as f64,
+            total.longest_exit_queue as f64 / runs
+        );
+    }
+
+    if scenario.vehicles.iter().any(|v| v.charging.is_some()) {
+        print_charging(runs, total);
+    }
+
+    if let Some(hours) = scenario.opening_hours {
+        print_closing(runs, hours, total);
+    }
+
+    if let Some((layout, _)) = scenario.layout {
+        print_layout(runs, scenario, layout, total);
+    }
+
+    if let Some(permits) = scenario.permits {
+        print_permits(runs, scenario, permits, total);
+    }
+
+    if scenario.tariff.is_some() {
+        print_revenue(capacity, runs, scenario, total);
+    }
+
+    // The class that occupies the most spot time is the one that the lot is being sized for,
+    // while the class that spends the most time waiting is the one that feels the queue
+    let largest = |key: fn(&ClassStats) -> u64| {
+        scenario
+            .classes
+            .iter()
+            .zip(&total.classes)
+            .filter(|(_, stats)| key(stats) != 0)
+            .max_by_key(|(_, stats)| key(stats))
+            .map(|(class, _)| class.name.as_str())
+    };
+    if let Some(name) = largest(|stats| stats.spot_time) {
+        eprintln!("Largest share of the demand for spots: {name}");
+    }
+    if let Some(name) = largest(|stats| stats.wait_time) {
+        eprintln!("Largest share of the time spent queueing: {name}");
+    }
+}
+
+/// Prints the waiting times of the cars at each priority level, from the highest priority down,
+/// unless every car is served in the order it arrived.
+fn print_priorities(runs: f64, scenario: &Scenario, total: &RunStats) {
+    if matches!(scenario.discipline, QueueDiscipline::Fifo)
+        && scenario
+            .classes
+            .iter()
+            .all(|class| class.priority == scenario.classes[0].priority)
+    {
+        return;
+    }
+
+    let mut priorities = scenario
+        .classes
+        .iter()
+        .map(|class| class.priority)
+        .collect::<Vec<_>>();
+    priorities.sort_unstable_by(|a, b| b.cmp(a));
+    priorities.dedup();
+
+    eprintln!(
+        "{:<16}{:>10}{:>14}{:>18}",
+        "Priority", "Parked", "Avg wait (s)", "Longest wait (s)"
+    );
+    for priority in priorities {
+        let mut stats = ClassStats::default();
+        for (_, class) in scenario
+            .classes
+            .iter()
+            .zip(&total.classes)
+            .filter(|(class, _)| class.priority == priority)
+        {
+            stats.add(class);
+        }
+        eprintln!(
+            "{:<16}{:>10.1}{:>14.1}{:>18}",
+            priority,
+            stats.parked as f64 / runs,
+            stats.average_wait(),
+            stats.longest_wait
+        );
+    }
+}
+
+/// Prints the size, utilisation and queueing of each pool,
+/// and how many of the parked cars each lot absorbed if the pools are a network of lots.
+fn print_pools(capacity: usize, runs: f64, scenario: &Scenario, total: &RunStats) {
+    let sizes = scenario.pool_sizes(capacity);
+    let heading = if scenario.routing.is_some() {
+        "Lot"
+    } else {
+        "Pool"
+    };
+    eprintln!(
+        "\n{:<16}{:>10}{:>10}{:>14}{:>14}{:>10}",
+        heading, "Spots", "Parked", "Utilisation", "Queue-hours", "Queued"
+    );
+    for ((pool, stats), size) in scenario.pools.iter().zip(&total.pools).zip(sizes) {
+        let observed = scenario.steps.saturating_sub(scenario.warm_up);
+        let utilisation = stats.spot_time as f64 / runs / (size as f64 * observed as f64).max(1.0);
+        eprintln!(
+            "{:<16}{:>10}{:>10.1}{:>13.1}%{:>14.1}{:>10.1}",
+            pool.name,
+            size,
+            stats.parked as f64 / runs,
+            utilisation * 100.0,
+            stats.wait_time as f64 / runs / 3600.0,
+            stats.queued as f64 / runs,
+        );
+    }
+    if scenario.routing.is_some() {
+        let parked = total
+            .pools
+            .iter()
+            .map(|pool| pool.parked)
+            .sum::<u64>()
+            .max(1);
+        let shares = scenario
+            .pools
+            .iter()
+            .zip(&total.pools)
+            .map(|(pool, stats)| {
+                format!(
+                    "{} {:.1}%",
+                    pool.name,
+                    stats.parked as f64 / parked as f64 * 100.0
+                )
+            })
+            .collect::<Vec<_>>();
+        eprintln!(
+            "Share of the parked cars absorbed by each lot: {}",
+            shares.join(", ")
+        );
+    }
+    eprintln!();
+}
+
+/// Prints how much charging the EVs got.
+fn print_charging(runs: f64, total: &RunStats) {
+    eprintln!(
+        "EVs charged per run: {:.1} ({:.1} parked without a charger), {:.1} kWh delivered, {:.1} minutes of charging on average",
+        total.charged as f64 / runs,
+        total.uncharged as f64 / runs,
+        total.energy as f64 / runs / 1000.0,
+        total.charge_time as f64 / total.charged.max(1) as f64 / 60.0
+    );
+    if total.moved + total.stayed_plugged_in > 0 {
+        eprintln!(
+            "EVs moved to a regular spot after charging per run: {:.1} ({:.1} stayed plugged in for lack of a spot)",
+            total.moved as f64 / runs,
+            total.stayed_plugged_in as f64 / runs
+        );
+    }
+}
+
+/// Prints how many customers were lost per run, and why.
+fn print_lost(runs: f64, total: &RunStats) {
+    let balked = total.classes.iter().map(|c| c.balked).sum::<u64>() as f64 / runs;
+    let reneged = total.classes.iter().map(|c| c.reneged).sum::<u64>() as f64 / runs;
+    let turned_away = total.classes.iter().map(|c| c.turned_away).sum::<u64>() as f64 / runs;
+    let priced_out = total.classes.iter().map(|c| c.priced_out).sum::<u64>() as f64 / runs;
+    let gave_up = total.classes.iter().map(|c| c.gave_up).sum::<u64>() as f64 / runs;
+    let missed_event = total.classes.iter().map(|c| c.missed_event).sum::<u64>() as f64 / runs;
+    let lost = balked + reneged + turned_away + priced_out + gave_up + missed_event;
+    if lost > 0.0 {
+        eprintln!(
+            "Lost customers per run: {lost:.1} ({balked:.1} balked, {reneged:.1} reneged, {turned_away:.1} turned away, {priced_out:.1} priced out, {gave_up:.1} gave up cruising, {missed_event:.1} missed their event)",
+        );
+    }
+}
+
+/// Prints how many cars of each class the admission policy held back, and how long the sign was on for.
+fn print_admission(runs: f64, scenario: &Scenario, admission: &Admission, total: &RunStats) {
+    let held_back = scenario
+        .classes
+        .iter()
+        .zip(&total.classes)
+        .map(|(class, stats)| format!("{} {:.1}", class.name, stats.held_back as f64 / runs))
+        .collect::<Vec<_>>();
+    eprintln!(
+        "Cars held back by the admission policy per run: {}",
+        held_back.join(", ")
+    );
+    if admission.full_sign.is_some() {
+        eprintln!(
+            "Time with the \"LOT FULL\" sign on per run: {:.1} minutes",
+            total.sign_time as f64 / runs / 60.0
+        );
+    }
+}
+
+/// Prints how much driving around the block a full lot causes.
+fn print_cruising(runs: f64, cruising: Cruising, total: &RunStats) {
+    eprintln!(
+        "Drivers cruising for a spot per run: {:.1} ({:.1} retries, {:.1} minutes of cruising on average)",
+        total.cruised as f64 / runs,
+        total.retries as f64 / runs,
+        total.cruise_time as f64 / total.cruised.max(1) as f64 / 60.0
+    );
+    eprintln!(
+        "Extra distance travelled per run: {:.1} vehicle-km",
+        cruising.distance(total.cruise_time) / runs
+    );
+}
+
+/// Prints the average walking distance, and a heatmap of how much of the time each spot was taken.
+/// Each row of spots is a line, with a character for each spot from ` ` (never taken) to `@` (always taken).
+fn print_layout(runs: f64, scenario: &Scenario, layout: Layout, total: &RunStats) {
+    const SHADES: &[u8] = b" .:-=+*#%@";
+
+    eprintln!(
+        "Average walk to the entrance: {:.1} m",
+        total.walking_distance / total.walked.max(1) as f64
+    );
+
+    let observed = runs * f64::from(scenario.steps.saturating_sub(scenario.warm_up));
+    eprintln!("Spot utilisation (` ` is never taken, `@` is always taken):");
+    // Rows never span two levels, so each level is split into rows separately
+    let level_size = layout.level_size.unwrap_or(total.spot_busy.len()).max(1);
+    for (level, spots) in total.spot_busy.chunks(level_size).enumerate() {
+        if layout.level_size.is_some() {
+            eprintln!("Level {level}:");
+        }
+        for (row_index, row) in spots.chunks(layout.row_length).enumerate() {
+            let shades = row
+                .iter()
+                .map(|&busy| {
+                    let utilisation = busy as f64 / observed.max(1.0);
+                    SHADES[((utilisation * SHADES.len() as f64) as usize).min(SHADES.len() - 1)]
+                        as char
+                })
+                .collect::<String>();
+            eprintln!("{row_index:>4} |{shades}|");
+        }
+    }
+}
+
+/// Prints what happened to the cars that were still in the lot or its queue at closing time.
+fn print_closing(runs: f64, hours: OpeningHours, total: &RunStats) {
+    let closed_out = total.classes.iter().map(|c| c.closed_out).sum::<u64>();
+    eprintln!(
+        "Cars still queued at closing per run: {:.1}",
+        closed_out as f64 / runs
+    );
+    match hours.at_closing {
+        AtClosing::Leave => eprintln!(
+            "Cars forced to leave at closing per run: {:.1}",
+            total.forced_departures as f64 / runs
+        ),
+        AtClosing::Overstay => eprintln!(
+            "Cars parked past closing per run: {:.1}",
+            total.overstays as f64 / runs
+        ),
+    }
+}
+
+/// Prints how often permit holders found every reserved spot taken and how often they never parked,
+/// and how often walk-ins used the reserved spots.
+fn print_permits(runs: f64, scenario: &Scenario, permits: Permits, total: &RunStats) {
+    let holders = || {
+        scenario
+            .classes
+            .iter()
+            .zip(&total.classes)
+            .filter(|(class, _)| class.permit)
+            .map(|(_, stats)| stats)
+    };
+    let permit_arrivals = holders().map(|stats| stats.arrived).sum::<u64>().max(1) as f64;
+    // Holders who find the reserved spots taken may still park in a walk-in spot
+    let unparked = holders()
+        .map(|stats| stats.lost() + stats.queued)
+        .sum::<u64>() as f64;
+    eprintln!(
+        "Permit holders arriving to find every reserved spot taken per run: {:.1} ({:.1}% of permit holders)",
+        total.no_permit_spot as f64 / runs,
+        total.no_permit_spot as f64 / permit_arrivals * 100.0
+    );
+    eprintln!(
+        "Permit holders lost or still queued per run: {:.1} ({:.1}% of permit holders)",
+        unparked / runs,
+        unparked / permit_arrivals * 100.0
+    );
+    if permits.release_after.is_some() {
+        eprintln!(
+            "Walk-ins parked in released reserved spots per run: {:.1}",
+            total.released_parked as f64 / runs
+        );
+    }
+}
+
+/// Prints the revenue from each class, and the revenue lost to cars that never parked.
+fn print_revenue(capacity: usize, runs: f64, scenario: &Scenario, total: &RunStats) {
+    eprintln!(
+        "\n{:<16}{:>14}{:>14}{:>14}",
+        "Class", "Revenue", "Per car", "Lost revenue"
+    );
+    for (class, stats) in scenario.classes.iter().zip(&total.classes) {
+        eprintln!(
+            "{:<16}{:>14.2}{:>14.2}{:>14.2}",
+            class.name,
+            stats.revenue / runs,
+            stats.revenue / stats.parked.max(1) as f64,
+            stats.lost_revenue() / runs,
+        );
+    }
+
+    let revenue = total.classes.iter().map(|c| c.revenue).sum::<f64>() / runs;
+    let lost = total
+        .classes
+        .iter()
+        .map(ClassStats::lost_revenue)
+        .sum::<f64>()
+        / runs;
+    let spots = scenario.pool_sizes(capacity).iter().sum::<usize>().max(1);
+    let days = scenario.steps as f64 / DAY as f64;
+    eprintln!(
+        "Revenue per run: {revenue:.2} ({:.2} per spot per day), with {lost:.2} lost to cars that never parked\n",
+        revenue / spots as f64 / days
+    );
+}
+
+/// Prints the main results of several lots side by side, such as a lot with dynamic pricing
+/// and a lot with a static price. Each lot is given with a label, its capacity and its statistics.
+pub fn print_comparison(runs: u32, lots: &[(&str, usize, &Scenario, &RunStats)]) {
+    let runs = runs as f64;
+    let row = |name: &str, value: &dyn Fn(usize, &Scenario, &RunStats) -> f64| {
+        eprint!("{name:<28}");
+        for &(_, capacity, scenario, stats) in lots {
+            eprint!("{:>14.1}", value(capacity, scenario, stats));
+        }
+        eprintln!();
+    };
+    let sum = |stats: &RunStats, key: fn(&ClassStats) -> u64| {
+        stats.classes.iter().map(key).sum::<u64>() as f64
+    };
+
+    eprint!("\n{:<28}", "");
+    for (label, ..) in lots {
+        eprint!("{label:>14}");
+    }
+    eprintln!();
+    row("Spots", &|capacity, scenario, _| {
+        scenario.pool_sizes(capacity).iter().sum::<usize>() as f64
+    });
+    row("Revenue per run", &|_, _, stats| {
+        stats.classes.iter().map(|c| c.revenue).sum::<f64>() / runs
+    });
+    row("Parked per run", &|_, _, stats| {
+        sum(stats, |c| c.parked) / runs
+    });
+    row("Priced out per run", &|_, _, stats| {
+        sum(stats, |c| c.priced_out) / runs
+    });
+    row("Lost customers per run", &|_, _, stats| {
+        sum(stats, ClassStats::lost) / runs
+    });
+    row("Average wait (s)", &|_, _, stats| {
+        sum(stats, |c| c.wait_time)
+            / (sum(stats, |c| c.arrived) - sum(stats, |c| c.balked) - sum(stats, |c| c.priced_out))
+                .max(1.0)
+    });
+    row("Longest queue per run", &|_, _, stats| {
+        stats.longest_queue_total as f64 / runs
+    });
+}
+
+/// Prints the parked cars, lost customers and average wait of each class in several lots side by side.
+/// Every lot must have the same classes.
+pub fn print_class_comparison(runs: u32, lots: &[(&str, usize, &Scenario, &RunStats)]) {
+    let runs = runs as f64;
+    let Some(&(_, _, scenario, _)) = lots.first() else {
+        return;
+    };
+
+    eprint!("\n{:<28}", "Class");
+    for (label, ..) in lots {
+        eprint!("{label:>14}");
+    }
+    eprintln!();
+    for (i, class) in scenario.classes.iter().enumerate() {
+        let row = |name: &str, value: &dyn Fn(&ClassStats) -> f64| {
+            eprint!("{:<28}", format!("{} {name}", class.name));
+            for (.., stats) in lots {
+                eprint!("{:>14.1}", value(&stats.classes[i]));
+            }
+            eprintln!();
+        };
+        row("parked per run", &|stats| stats.parked as f64 / runs);
+        row("lost per run", &|stats| stats.lost() as f64 / runs);
+        row("average wait (s)", &ClassStats::average_wait);
+    }
+}
@@ -0,0 +1,421 @@
+use std::sync::Arc;
+
+use crate::{
+    admission::Admission,
+    cli::Cli,
+    customer_class::CustomerClass,
+    gate::GateConfig,
+    layout::{Layout, SpotChoice, SpotMap},
+    network::Routing,
+    opening_hours::OpeningHours,
+    parking_lot::{PoolSize, PoolSpec, PooledLot, UNITS_PER_SPOT},
+    permit::{PermitHolders, Permits},
+    pricing::Pricing,
+    queue::{Balking, Cruising, Overflow, QueueDiscipline},
+    schedule::{CapacityChange, ClosureSpec},
+    special_event::EventSpec,
+    tariff::Tariff,
+    triangular_distribution::StaySampler,
+    vehicle::{VehicleSpec, VehicleType},
+    warm_up::{InitialOccupancy, WarmUp},
+};
+
+/// Everything about the simulated lot that does not change between runs.
+/// This is built once from the command line arguments and shared by every simulation run.
+#[derive(Clone)]
+pub struct Scenario {
+    /// The number of ticks to simulate.
+    pub steps: u32,
+    /// The customer classes sharing the lot.
+    /// The default traffic is the first class, unless it has no arrivals.
+    pub classes: Vec<CustomerClass>,
+    /// The special events, alongside the index of the class that their cars are counted in.
+    pub events: Vec<(usize, EventSpec)>,
+    /// How arriving drivers decide not to join the queue, if they ever do.
+    pub balking: Option<Balking>,
+    /// The mean time that drivers are willing to wait in the queue, if they ever give up.
+    pub patience: Option<f32>,
+    /// The number of cars that fit in the entrance lane, if it is limited.
+    pub queue_storage: Option<usize>,
+    /// What happens to cars that arrive when the entrance lane is full.
+    pub overflow: Overflow,
+    /// How drivers circle the block when the lot is full, if they don't queue at the entrance.
+    pub cruising: Option<Cruising>,
+    /// The order in which queued cars get to park.
+    pub discipline: QueueDiscipline,
+    /// The policy that stops admitting cars before the lot is full, if there is one.
+    pub admission: Option<Admission>,
+    /// The hours during which the lot is open, if it ever closes.
+    pub opening_hours: Option<OpeningHours>,
+    /// The entrance gates, if they are modelled.
+    pub entry_gates: Option<GateConfig>,
+    /// The exit gates, if they are modelled.
+    pub exit_gates: Option<GateConfig>,
+    /// The pools of spots that make up the lot, or the lots that make up the network.
+    /// Exactly one of these gets the remainder of the capacity being tested.
+    pub pools: Vec<PoolSpec>,
+    /// How drivers choose between the lots, if the pools are separate lots in a network.
+    pub routing: Option<Routing>,
+    /// The types of vehicles arriving at the lot.
+    pub vehicles: Vec<VehicleType>,
+    /// The spots reserved for permit holders, if there are any.
+    pub permits: Option<Permits>,
+    /// The prices charged for parking, if revenue is being calculated.
+    pub tariff: Option<Tariff>,
+    /// The dynamic pricing policy and the drivers' response to it, if prices change.
+    pub pricing: Option<Pricing>,
+    /// How full the lot is at the start of each run, if it doesn't start empty.
+    pub initial_occupancy: Option<InitialOccupancy>,
+    /// The number of seconds at the start of each run whose statistics are discarded.
+    pub warm_up: u32,
+    /// The positions of the spots and how drivers pick between them, if individual spots are modelled.
+    pub layout: Option<(Layout, SpotChoice)>,
+    /// The scheduled closures and reopenings of spots, sorted by time.
+    pub capacity_changes: Arc<[CapacityChange]>,
+}
+
+impl Scenario {
+    /// Builds the scenario, or returns an error if the options don't fit together
+    /// (e.g. a vehicle uses a pool that doesn't exist).
+    pub fn new(cli: &Cli) -> Result<Self, String> {
+        // Only computed once, since the skewed distribution is relatively expensive to set up
+        let stay = if cli.exponential_stay {
+            StaySampler::Exponential(cli.max_stay as f32 / 2.0)
+        } else {
+            StaySampler::new(cli.max_stay, cli.skew)
+        };
+
+        let (classes, events) = Self::classes(cli, &stay);
+
+        let balking = (cli.balk_per_car > 0.0 || cli.balk_when_full > 0.0).then_some(Balking {
+            per_queued: cli.balk_per_car,
+            when_full: cli.balk_when_full,
+        });
+
+        let routing = (!cli.lots.is_empty()).then_some(cli.routing);
+        let mut pools = if routing.is_some() {
+            cli.lots.clone()
+        } else if cli.pools.is_empty() {
+            vec![PoolSpec::new("regular", PoolSize::Remainder)]
+        } else {
+            cli.pools.clone()
+        };
+        let vehicles = Self::vehicles(&cli.vehicles, &pools, routing)?;
+        // The reserved spots are added after the vehicles are resolved,
+        // so that walk-ins never list them as one of their pools
+        let permits = cli.permits.map(|count| {
+            pools.push(PoolSpec::new("permit", PoolSize::Fixed(count)));
+            Permits {
+                pool: pools.len() - 1,
+                // Spots are held from the time that permit holders start arriving
+                hold_from: classes
+                    .iter()
+                    .filter(|class| class.permit)
+                    .find_map(|class| class.hours)
+                    .map_or(0, |(from, _)| from),
+                release_after: cli.release_after,
+                holders: cli.show_up.map(|show_up| PermitHolders {
+                    sold: cli.permits_sold.unwrap_or(count),
+                    show_up,
+                }),
+            }
+        });
+
+        Self::check(cli, &vehicles)?;
+        let capacity_changes = Self::capacity_changes(&cli.closures, &pools, cli.duration)?;
+        let admission = Self::admission(cli, &classes)?;
+
+        Ok(Self {
+            steps: cli.duration,
+            classes,
+            events,
+            balking,
+            patience: cli.patience,
+            queue_storage: cli.queue_storage,
+            overflow: cli.overflow,
+            cruising: cli.cruise.map(|mean_delay| Cruising {
+                mean_delay,
+                max_retries: cli.max_retries,
+                speed: cli.cruise_speed,
+            }),
+            discipline: cli.queue_discipline,
+            admission,
+            opening_hours: cli.open_hours.map(|hours| OpeningHours {
+                hours,
+                at_closing: cli.at_closing,
+            }),
+            entry_gates: cli.entry_service.map(|service| GateConfig {
+                count: cli.entry_gates as usize,
+                service,
+            }),
+            exit_gates: cli.exit_service.map(|service| GateConfig {
+                count: cli.exit_gates as usize,
+                service,
+            }),
+            pools,
+            routing,
+            vehicles,
+            permits,
+            tariff: cli.tariff,
+            pricing: cli.surge.clone().map(|surge| Pricing {
+                surge,
+                arrival_elasticity: cli.price_elasticity,
+                stay_elasticity: cli.stay_elasticity,
+            }),
+            initial_occupancy: cli.initial_occupancy,
+            // An automatic warm-up period is detected later, since it needs the rest of the scenario
+            warm_up: match cli.warm_up {
+                Some(WarmUp::Fixed(seconds)) => seconds,
+                Some(WarmUp::Auto) | None => 0,
+            },
+            layout: cli.layout.map(|layout| (layout, cli.spot_choice)),
+            capacity_changes,
+        })
+    }
+
+    /// Returns an error if options that are each valid on their own don't fit together.
+    fn check(cli: &Cli, vehicles: &[VehicleType]) -> Result<(), String> {
+        if let Some(WarmUp::Fixed(seconds)) = cli.warm_up
+            && seconds >= cli.duration
+        {
+            return Err(format!(
+                "the warm-up period ({seconds} seconds) must be shorter than the --duration"
+            ));
+        }
+
+        // Turned-away cars never count as left in the queue, so only --max-lost (or their cost) limits them
+        if cli.queue_storage.is_some()
+            && matches!(cli.overflow, Overflow::TurnAway)
+            && cli.max_lost.is_none()
+            && cli.spot_cost.is_none()
+        {
+            return Err("cars turned away from a full --queue-storage are lost, \
+                so --max-lost (or --spot-cost) is needed to limit them"
+                .into());
+        }
+
+        // Spots in a layout are taken one at a time, without modelling which spots are next to each other
+        if cli.layout.is_some() && vehicles.iter().any(|v| v.units != UNITS_PER_SPOT) {
+            return Err(
+                "every vehicle must take up exactly one spot (size=1) when the lot has a --layout"
+                    .into(),
+            );
+        }
+
+        Ok(())
+    }
+
+    /// Resolves the pool names of each vehicle type.
+    /// In a network of lots, vehicles can use every lot by default, in the order given by the routing.
+    fn vehicles(
+        specs: &[VehicleSpec],
+        pools: &[PoolSpec],
+        routing: Option<Routing>,
+    ) -> Result<Vec<VehicleType>, String> {
+        let remainder = pools
+            .iter()
+            .filter(|pool| matches!(pool.size, PoolSize::Remainder))
+            .count();
+        if remainder != 1 {
+            return Err(
+                "exactly one pool must have no share or size, so that it can be sized by the search"
+                    .into(),
+            );
+        }
+        let main_pool = Self::main_pool_in(pools);
+        // Drivers try every lot in the routing order, unless their vehicle lists its own order of pools
+        let default_pools = || {
+            if let Some(routing) = routing {
+                let mut lots = (0..pools.len()).collect::<Vec<_>>();
+                routing.order(pools, &mut lots);
+                lots
+            } else {
+                vec![main_pool]
+            }
+        };
+
+        if specs.is_empty() {
+            return Ok(vec![VehicleType {
+                share: 1.0,
+                pools: default_pools(),
+                charging: None,
+                move_to: Vec::new(),
+                units: UNITS_PER_SPOT,
+            }]);
+        }
+
+        specs
+            .iter()
+            .map(|spec| {
+                let allowed = if spec.pools.is_empty() {
+                    default_pools()
+                } else {
+                    spec.pools
+                        .iter()
+                        .map(|name| {
+                            pools
+                                .iter()
+                                .position(|pool| &pool.name == name)
+                                .ok_or_else(|| {
+                                    format!(
+                                        "there is no pool named `{name}` for vehicle `{}`",
+                                        spec.name
+                                    )
+                                })
+                        })
+                        .collect::<Result<Vec<_>, _>>()?
+                };
+                Ok(VehicleType {
+                    share: spec.share,
+                    move_to: allowed
+                        .iter()
+                        .copied()
+                        .filter(|&pool| pools[pool].power == 0.0)
+                        .collect(),
+                    pools: allowed,
+                    charging: spec.charging.clone(),
+                    units: spec.units,
+                })
+            })
+            .collect()
+    }
+
+    /// Builds the customer classes, including a class for the default traffic and a class for each special event,
+    /// alongside the special events and the indices of their classes.
+    fn classes(cli: &Cli, stay: &StaySampler) -> (Vec<CustomerClass>, Vec<(usize, EventSpec)>) {
+        let mut classes = Vec::with_capacity(cli.classes.len() + cli.events.len() + 1);
+        if cli.cars_per_hour > 0.0 {
+            classes.push(CustomerClass {
+                name: "default".into(),
+                cars_per_second: cli.cars_per_hour / 3600.0,
+                hours: None,
+                stay: stay.clone(),
+                departure: None,
+                permit: false,
+                priority: 0,
+            });
+        }
+        classes.extend(cli.classes.iter().map(|spec| spec.build(stay)));
+        let events = cli
+            .events
+            .iter()
+            .map(|spec| {
+                classes.push(spec.class(stay));
+                (classes.len() - 1, spec.clone())
+            })
+            .collect();
+
+        (classes, events)
+    }
+
+    /// Resolves the class names of the admission policy, if there is one.
+    fn admission(cli: &Cli, classes: &[CustomerClass]) -> Result<Option<Admission>, String> {
+        if cli.reserve.is_none() && cli.full_sign.is_none() {
+            return Ok(None);
+        }
+
+        let mut reserved_for = vec![false; classes.len()];
+        let mut reserve = 0;
+        if let Some(spec) = &cli.reserve {
+            reserve = spec.spots.saturating_mul(UNITS_PER_SPOT);
+            if spec.classes.is_empty() {
+                for (reserved, class) in reserved_for.iter_mut().zip(classes) {
+                    *reserved = class.priority > 0;
+                }
+            }
+            for name in &spec.classes {
+                let class = classes
+                    .iter()
+                    .position(|class| &class.name == name)
+                    .ok_or_else(|| {
+                        format!("there is no class named `{name}` to reserve spots for")
+                    })?;
+                reserved_for[class] = true;
+            }
+        }
+
+        Ok(Some(Admission {
+            reserve,
+            reserved_for,
+            full_sign: cli.full_sign,
+        }))
+    }
+
+    /// Expands the closures into the changes in capacity that they cause, sorted by time.
+    fn capacity_changes(
+        closures: &[ClosureSpec],
+        pools: &[PoolSpec],
+        steps: u32,
+    ) -> Result<Arc<[CapacityChange]>, String> {
+        let main_pool = Self::main_pool_in(pools);
+        let mut changes = Vec::new();
+        for closure in closures {
+            let pool = match &closure.pool {
+                Some(name) => pools
+                    .iter()
+                    .position(|pool| &pool.name == name)
+                    .ok_or_else(|| format!("there is no pool named `{name}` to close"))?,
+                None => main_pool,
+            };
+            changes.extend(closure.changes(pool, steps));
+        }
+        // Reopenings go first, so that the number of closed spots never overshoots
+        changes.sort_by_key(|change| (change.time, change.closed));
+        Ok(changes.into())
+    }
+
+    fn main_pool_in(pools: &[PoolSpec]) -> usize {
+        pools
+            .iter()
+            .position(|pool| matches!(pool.size, PoolSize::Remainder))
+            .unwrap_or_default()
+    }
+
+    /// The index of the pool that is sized by the search.
+    pub fn main_pool(&self) -> usize {
+        Self::main_pool_in(&self.pools)
+    }
+
+    /// The number of spots in each pool for a given total capacity.
+    /// Fixed pools don't count towards the capacity, so they are added on top of it.
+    pub fn pool_sizes(&self, capacity: usize) -> Vec<usize> {
+        let mut sizes = self
+            .pools
+            .iter()
+            .map(|pool| match pool.size {
+                PoolSize::Remainder => 0,
+                PoolSize::Share(share) => (capacity as f32 * share).round() as usize,
+                PoolSize::Fixed(size) => size,
+            })
+            .collect::<Vec<_>>();
+
+        let shared = self
+            .pools
+            .iter()
+            .zip(&sizes)
+            .filter(|(pool, _)| matches!(pool.size, PoolSize::Share(_)))
+            .map(|(_, size)| size)
+            .sum::<usize>();
+        sizes[self.main_pool()] = capacity.saturating_sub(shared);
+
+        sizes
+    }
+
+    /// Creates an empty lot for a given total capacity.
+    /// Individual spots aren't tracked for a lot with unlimited capacity.
+    pub fn lot(&self, capacity: usize) -> PooledLot {
+        let sizes = self.pool_sizes(capacity);
+        // Only the event-based simulator has vehicle types,
+        // so cars in the other simulators park like the first (and only) one
+        let lot = PooledLot::new(sizes.iter().copied(), self.vehicles[0].pools.clone());
+        match self.layout {
+            Some((layout, choice)) if capacity != usize::MAX => lot.with_spots(SpotMap::new(
+                &layout,
+                choice,
+                &sizes,
+                (self.warm_up, self.steps),
+            )),
+            _ => lot,
+        }
+    }
+}
@@ -0,0 +1,136 @@
+use std::{str::FromStr, sync::Arc};
+
+use crate::customer_class::{DAY, parse_hours};
+
+/// The names of the days of the week, in the order that they are simulated.
+/// The simulation starts on a Monday at midnight.
+const WEEKDAYS: [&str; 7] = ["mon", "tue", "wed", "thu", "fri", "sat", "sun"];
+
+/// A recurring window of time during which some spots are closed (e.g. for cleaning or maintenance).
+///
+/// The format is `SPOTS[,hours=FROM-TO][,days=DAY/DAY/...][,pool=NAME]`,
+/// where the hours are hours of the day and the days are `mon` to `sun`.
+/// Without hours, the spots are closed all day; without days, they are closed every day.
+/// Without a pool, the spots are closed in the pool that gets the rest of the capacity.
+#[derive(Clone)]
+pub struct ClosureSpec {
+    pub spots: usize,
+    pub hours: (u32, u32),
+    /// A bit for each day of the week (starting with Monday) on which the spots are closed.
+    pub days: u8,
+    pub pool: Option<String>,
+}
+
+/// A change in the number of closed spots in a pool.
+#[derive(Clone, Copy)]
+pub struct CapacityChange {
+    pub time: u32,
+    pub pool: usize,
+    /// The number of spots that close (or reopen, if negative).
+    pub closed: isize,
+}
+
+impl ClosureSpec {
+    /// Expands the closure into the capacity changes that happen before a given end time,
+    /// where `pool` is the resolved index of the closure's pool.
+    pub fn changes(&self, pool: usize, steps: u32) -> impl Iterator<Item = CapacityChange> {
+        let (from, to) = self.hours;
+        // A window that wraps around midnight ends on the next day
+        let length = (to + DAY - from - 1) % DAY + 1;
+        let spots = self.spots.cast_signed();
+
+        (0..steps.div_ceil(DAY))
+            .filter(move |day| self.days & (1 << (day % 7)) != 0)
+            .flat_map(move |day| {
+                let start = day * DAY + from;
+                [
+                    CapacityChange {
+                        time: start,
+                        pool,
+                        closed: spots,
+                    },
+                    CapacityChange {
+                        time: start + length,
+                        pool,
+                        closed: -spots,
+                    },
+                ]
+            })
+            .filter(move |change| change.time < steps)
+    }
+}
+
+impl FromStr for ClosureSpec {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parts = s.split(',');
+        let spots = parts.next().unwrap_or_default();
+        let mut spec = Self {
+            spots: spots
+                .parse()
+                .map_err(|e| format!("invalid number of spots `{spots}`: {e}"))?,
+            hours: (0, DAY),
+            days: 0b111_1111,
+            pool: None,
+        };
+
+        for part in parts {
+            match part.split_once('=') {
+                Some(("hours", value)) => spec.hours = parse_hours(value)?,
+                Some(("days", value)) => spec.days = parse_days(value)?,
+                Some(("pool", value)) => spec.pool = Some(value.into()),
+                _ => return Err(format!("unknown closure option `{part}`")),
+            }
+        }
+
+        if spec.hours.0 == spec.hours.1 {
+            return Err("the closure must last for some time".into());
+        }
+
+        Ok(spec)
+    }
+}
+
+/// Parses days of the week in the format `DAY/DAY/...`, where each day is `mon` to `sun`,
+/// returning a bit for each day (starting with Monday).
+pub fn parse_days(value: &str) -> Result<u8, String> {
+    let mut days = 0;
+    for day in value.split('/') {
+        let index = WEEKDAYS
+            .iter()
+            .position(|&name| name == day.to_lowercase())
+            .ok_or_else(|| format!("invalid day `{day}`"))?;
+        days |= 1 << index;
+    }
+    Ok(days)
+}
+
+/// Keeps track of the scheduled capacity changes that have happened so far during a run.
+pub struct Schedule {
+    /// The changes, sorted by time. These are shared by every run.
+    changes: Arc<[CapacityChange]>,
+    next: usize,
+}
+
+impl Schedule {
+    pub fn new(changes: Arc<[CapacityChange]>) -> Self {
+        Self { changes, next: 0 }
+    }
+
+    /// The time of the next change, if there are any left.
+    pub fn next_time(&self) -> Option<u32> {
+        self.changes.get(self.next).map(|change| change.time)
+    }
+
+    /// Removes a change that happens by the given timestamp, returning it.
+    #[inline]
+    pub fn due(&mut self, timestamp: u32) -> Option<CapacityChange> {
+        let change = *self.changes.get(self.next)?;
+        if change.time > timestamp {
+            return None;
+        }
+        self.next += 1;
+        Some(change)
+    }
+}
@@ -1,27 +1,46 @@
+use std::collections::VecDeque;
+
 use crate::{
-    parking_lot::ParkingLot,
-    random_generator,
-    triangular_distribution::{TriangularPdf, TriangularPdfSampler},
+    customer_class::CustomerClass, parking_lot::ParkingLot, random_generator, schedule::Schedule,
+    triangular_distribution::TriangularPdf,
 };
 
 enum Pdf {
     Discrete(TriangularPdf),
-    Continuous(TriangularPdfSampler),
+    /// Stays are sampled from the distribution of each car's class when it parks.
+    Continuous,
 }
 
 pub trait Simulator {
     fn simulate<T: rand::Rng>(&mut self, rng: &mut T);
     fn cars_left(&self) -> usize;
+    /// The number of cars that gave up on parking, for simulators that model it.
+    fn cars_lost(&self) -> usize {
+        0
+    }
+    /// The number of permit holders that found no reserved spot, and the number of permit holders
+    /// that arrived, for simulators that model reserved spots.
+    fn permit_refusals(&self) -> (usize, usize) {
+        (0, 0)
+    }
+    /// The total number of seconds that cars spent in the queue,
+    /// for simulators that keep track of it.
+    fn wait_time(&self) -> u64 {
+        0
+    }
 }
 
 pub struct StandardSimulator<P: ParkingLot> {
     lot: P,
+    schedule: Schedule,
     clock: u32,
     steps: u32,
     max_time: u32,
-    incoming: usize,
+    /// The class of each car in the queue.
+    incoming: VecDeque<usize>,
     pdf: Pdf,
-    cars_per_second: f32,
+    /// The customer classes, which can only have their own stays in continuous mode.
+    classes: Vec<CustomerClass>,
 }
 
 impl<P: ParkingLot> StandardSimulator<P> {
@@ -29,27 +48,23 @@ impl<P: ParkingLot> StandardSimulator<P> {
         lot: P,
         max_time: u32,
         steps: u32,
-        cars_per_hour: f32,
+        classes: &[CustomerClass],
         continuous: bool,
-        skew: bool,
+        schedule: Schedule,
     ) -> Self {
         Self {
+            schedule,
             lot,
             steps,
             max_time,
             clock: 0,
-            incoming: 0,
+            incoming: VecDeque::new(),
             pdf: if continuous {
-                Pdf::Continuous(TriangularPdfSampler::new(
-                    0.0,
-                    (max_time / 2) as f32,
-                    max_time as f32,
-                    skew,
-                ))
+                Pdf::Continuous
             } else {
                 Pdf::Discrete(TriangularPdf::new(0, max_time / 2, max_time))
             },
-            cars_per_second: cars_per_hour / 3600.0,
+            classes: classes.to_vec(),
         }
     }
 }
@@ -57,10 +72,13 @@ impl<P: ParkingLot> StandardSimulator<P> {
 impl<P: ParkingLot> Simulator for StandardSimulator<P> {
     fn simulate<T: rand::Rng>(&mut self, rng: &mut T) {
         while self.clock < self.steps {
-            // Determine whether car likely arrives
-            let car_arrived = random_generator::event_occurred(rng, self.cars_per_second);
-            if car_arrived {
-                self.incoming += 1;
+            // Determine whether a car of each class likely arrives
+            for (index, class) in self.classes.iter().enumerate() {
+                if class.arrives_at(self.clock)
+                    && random_generator::event_occurred(rng, class.cars_per_second)
+                {
+                    self.incoming.push_back(index);
+                }
             }
 
             // Iterate over cars in lot
@@ -91,19 +109,25 @@ impl<P: ParkingLot> Simulator for StandardSimulator<P> {
                 self.lot.remove_index(index);
             }
 
+            // Close or reopen any scheduled spots, without evicting any cars
+            while let Some(change) = self.schedule.due(self.clock) {
+                self.lot.close(change.pool, change.closed);
+            }
+
             // Try to park car in queue
-            if self.incoming != 0 && self.lot.can_park() {
+            if self.lot.can_park()
+                && let Some(class) = self.incoming.pop_front()
+            {
                 // Continuous probability mode
-                if let Pdf::Continuous(pdf) = &self.pdf {
+                if matches!(self.pdf, Pdf::Continuous) {
                     // Randomly determine the leave time for this car
-                    let leave_time = pdf.sample(rng) + self.clock;
+                    let leave_time = self.classes[class].sample_stay(rng, self.clock) + self.clock;
                     let _ = self.lot.try_park(leave_time);
                 }
                 // Discrete probability mode (assignment)
                 else {
                     let _ = self.lot.try_park(self.clock);
                 }
-                self.incoming -= 1;
             }
 
             // Tick
@@ -112,6 +136,6 @@ impl<P: ParkingLot> Simulator for StandardSimulator<P> {
     }
 
     fn cars_left(&self) -> usize {
-        self.incoming
+        self.incoming.len()
     }
 }
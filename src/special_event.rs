@@ -0,0 +1,122 @@
+use std::str::FromStr;
+
+use crate::{
+    customer_class::{CustomerClass, DAY, parse_departure},
+    random_generator,
+    schedule::parse_days,
+    triangular_distribution::{StaySampler, TriangularPdfSampler},
+};
+
+/// A scheduled special event (e.g. a concert or a shift change at a factory)
+/// that brings a spike of arrivals before it starts and a mass departure once it ends.
+///
+/// The format is `NAME,cars=N,arrive=MIN/MODE/MAX,leave=MIN/MODE/MAX[,days=DAY/DAY/...]`,
+/// where `cars` is the expected number of cars per event,
+/// and the arrival and departure times are hours of the day (e.g. `arrive=18/19.5/20,leave=22.5/22.6/23.5`).
+/// Without days, the event happens every day.
+/// Each time the event happens, it ends at a single time drawn from the departure times,
+/// and its cars all leave within [`Self::DEPARTURE_SPREAD`] seconds of it.
+#[derive(Clone)]
+pub struct EventSpec {
+    pub name: String,
+    pub cars: f32,
+    /// The distribution of the arrival times, in seconds of the day.
+    pub arrival: TriangularPdfSampler,
+    /// The distribution of the time at which the event ends, in seconds of the day.
+    pub departure: TriangularPdfSampler,
+    /// A bit for each day of the week (starting with Monday) on which the event happens.
+    pub days: u8,
+}
+
+impl EventSpec {
+    /// The most seconds after the end of the event that its cars leave,
+    /// as the crowd makes its way back to the lot.
+    pub const DEPARTURE_SPREAD: u32 = 600;
+
+    /// The customer class that the event's cars are counted in.
+    /// Its cars don't arrive at a steady rate or stay for a drawn duration,
+    /// so their arrivals and departures are generated separately (see [`Self::occurrences`]).
+    pub fn class(&self, default_stay: &StaySampler) -> CustomerClass {
+        CustomerClass {
+            name: self.name.clone(),
+            cars_per_second: 0.0,
+            hours: None,
+            stay: default_stay.clone(),
+            departure: None,
+            permit: false,
+            priority: 0,
+        }
+    }
+
+    /// Generates the arrival times of the event's cars before a given end time, in no particular order,
+    /// and the time at which the event ends on each day (`u32::MAX` on days without it).
+    /// The number of cars at each event follows a Poisson distribution.
+    /// An event that ends earlier in the day than its cars arrive on average ends on the next day.
+    pub fn occurrences<R: rand::Rng>(&self, rng: &mut R, steps: u32) -> (Vec<u32>, Vec<u32>) {
+        let next_day = if self.departure.mean() < self.arrival.mean() {
+            DAY
+        } else {
+            0
+        };
+        let mut arrivals = Vec::new();
+        let mut ends = Vec::new();
+        for day in 0..steps.div_ceil(DAY) {
+            if self.days & (1 << (day % 7)) == 0 {
+                ends.push(u32::MAX);
+                continue;
+            }
+            let start = day * DAY;
+            ends.push(start.saturating_add(next_day + self.departure.sample(rng)));
+            let cars = random_generator::poisson(rng, f64::from(self.cars));
+            // Cars arrive on the day of the event, even if the arrivals go up to midnight
+            arrivals.extend(
+                (0..cars)
+                    .map(|_| start + self.arrival.sample(rng).min(DAY - 1))
+                    .filter(|&time| time < steps),
+            );
+        }
+        (arrivals, ends)
+    }
+
+    /// Generates the departure time of a car whose event ends at a given time.
+    pub fn departure<R: rand::Rng>(rng: &mut R, end: u32) -> u32 {
+        end.saturating_add(rng.random_range(0..=Self::DEPARTURE_SPREAD))
+    }
+}
+
+impl FromStr for EventSpec {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parts = s.split(',');
+        let name = parts.next().unwrap_or_default();
+        if name.is_empty() || name.contains('=') {
+            return Err("the event must start with a name".into());
+        }
+
+        let mut cars = None;
+        let mut arrival = None;
+        let mut departure = None;
+        let mut days = 0b111_1111;
+        for part in parts {
+            match part.split_once('=') {
+                Some(("cars", value)) => match value.parse::<f32>() {
+                    Ok(count) if count > 0.0 => cars = Some(count),
+                    _ => return Err(format!("invalid number of cars `{value}`")),
+                },
+                Some(("arrive", value)) => arrival = Some(parse_departure(value)?),
+                Some(("leave", value)) => departure = Some(parse_departure(value)?),
+                Some(("days", value)) => days = parse_days(value)?,
+                _ => return Err(format!("unknown event option `{part}`")),
+            }
+        }
+
+        Ok(Self {
+            name: name.into(),
+            cars: cars.ok_or("the event needs a number of cars")?,
+            arrival: arrival.ok_or("the event needs arrival times")?,
+            departure: departure.ok_or("the event needs departure times")?,
+            days,
+        })
+    }
+}
@@ -0,0 +1,84 @@
+use std::str::FromStr;
+
+use crate::customer_class::DAY;
+
+/// The prices charged for parking.
+///
+/// The format is `RATE[,cap=PRICE][,free=SECONDS][,per=SECONDS]`,
+/// where the rate is the price per hour, the cap is the most charged for any 24 hours,
+/// the free period is the time at the start of each stay that isn't charged,
+/// and stays are billed in increments of `per` seconds (an hour by default).
+#[derive(Clone, Copy)]
+pub struct Tariff {
+    pub hourly_rate: f32,
+    pub daily_cap: Option<f32>,
+    pub free_period: u32,
+    pub increment: u32,
+}
+
+impl Tariff {
+    /// The price of a stay of a given number of seconds.
+    /// The daily cap is applied to each full day, and to whatever is left over after them.
+    pub fn charge(&self, duration: u32) -> f32 {
+        let billable = duration.saturating_sub(self.free_period);
+        let Some(cap) = self.daily_cap else {
+            return self.uncapped(billable);
+        };
+
+        let days = billable / DAY;
+        days as f32 * cap.min(self.uncapped(DAY)) + cap.min(self.uncapped(billable % DAY))
+    }
+
+    fn uncapped(&self, billable: u32) -> f32 {
+        let increments = billable.div_ceil(self.increment);
+        increments as f32 * self.increment as f32 / 3600.0 * self.hourly_rate
+    }
+}
+
+impl FromStr for Tariff {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parts = s.split(',');
+        let rate = parts.next().unwrap_or_default();
+        let mut tariff = Self {
+            hourly_rate: rate
+                .parse()
+                .map_err(|e| format!("invalid hourly rate `{rate}`: {e}"))?,
+            daily_cap: None,
+            free_period: 0,
+            increment: 3600,
+        };
+
+        for part in parts {
+            match part.split_once('=') {
+                Some(("cap", value)) => {
+                    tariff.daily_cap = Some(
+                        value
+                            .parse()
+                            .map_err(|e| format!("invalid daily cap `{value}`: {e}"))?,
+                    );
+                }
+                Some(("free", value)) => {
+                    tariff.free_period = value
+                        .parse()
+                        .map_err(|e| format!("invalid free period `{value}`: {e}"))?;
+                }
+                Some(("per", value)) => match value.parse::<u32>() {
+                    Ok(increment) if increment > 0 => tariff.increment = increment,
+                    _ => return Err(format!("invalid billing increment `{value}`")),
+                },
+                _ => return Err(format!("unknown tariff option `{part}`")),
+            }
+        }
+
+        if tariff.hourly_rate < 0.0 {
+            return Err("the hourly rate can't be negative".into());
+        }
+        if tariff.daily_cap.is_some_and(|cap| cap < 0.0) {
+            return Err("the daily cap can't be negative".into());
+        }
+
+        Ok(tariff)
+    }
+}
@@ -1,3 +1,7 @@
+use std::sync::Arc;
+
+use crate::random_generator;
+
 pub struct TriangularPdf {
     a: u32,
     c: u32,
@@ -37,6 +41,7 @@ impl TriangularPdf {
     }
 }
 
+#[derive(Clone, Copy)]
 pub struct TriangularPdfSampler {
     a: f32,
     b: f32,
@@ -44,11 +49,10 @@ pub struct TriangularPdfSampler {
     ba: f32, // (b - a)
     ca: f32, // (c - a)
     bc: f32, // (b - c)
-    skew: bool,
 }
 
 impl TriangularPdfSampler {
-    pub fn new(a: f32, c: f32, b: f32, skew: bool) -> Self {
+    pub fn new(a: f32, c: f32, b: f32) -> Self {
         // assert!(a < c && c < b, "Parameters must satisfy a < c < b.");
         let ba = b - a;
         let ca = c - a;
@@ -61,17 +65,11 @@ impl TriangularPdfSampler {
             ba,
             ca,
             bc,
-            skew,
         }
     }
 
     pub fn sample<T: rand::Rng>(&self, rng: &mut T) -> u32 {
-        let rand = {
-            let rand: f32 = rng.random();
-            // Try to match the incorrect distribution of the assignment.
-            // Taking the cube root of the random number seems to decently approximate it.
-            if self.skew { rand.cbrt() } else { rand }
-        };
+        let rand: f32 = rng.random();
 
         let result = if rand < self.fc {
             // Invert the CDF for the rising edge:
@@ -88,4 +86,186 @@ impl TriangularPdfSampler {
         // Convert the continuous sample to an integer.
         result.round() as u32
     }
+
+    pub fn mean(&self) -> f32 {
+        // The mode is `a + (c - a)`
+        (self.a + (self.a + self.ca) + self.b) / 3.0
+    }
+
+    pub fn max(&self) -> u32 {
+        self.b.round() as u32
+    }
+}
+
+/// Samples stay durations from the distribution that is implied by the assignment's
+/// per-tick departure rule.
+///
+/// The discrete simulators check every parked car once per tick, and a car that has been parked
+/// for `d` ticks leaves with probability `pdf(d)` (or always, once `d` reaches the max stay).
+/// That rule treats the PDF as a hazard rate, so the resulting stay durations are **not**
+/// triangular. Instead of approximating that, the exact probability of leaving after each
+/// duration is precomputed here, and sampling is done by inverting the resulting CDF.
+#[derive(Clone)]
+pub struct ImpliedStaySampler {
+    /// `cdf[i]` is the probability that a car leaves after at most `i + 1` ticks.
+    cdf: Arc<[f64]>,
+}
+
+impl ImpliedStaySampler {
+    pub fn new(max_time: u32) -> Self {
+        let pdf = TriangularPdf::new(0, max_time / 2, max_time);
+        let mut cdf = Vec::with_capacity(max_time as usize);
+        // The probability that a car is still parked after the previous tick
+        let mut survival = 1.0f64;
+
+        // A car is first checked on the tick after it parks, so the shortest stay is one tick
+        for duration in 1..max_time {
+            let hazard = Self::hazard(pdf.pdf(duration));
+            survival *= 1.0 - hazard;
+            cdf.push(1.0 - survival);
+        }
+        // Any car that is still parked at the max stay is forced to leave
+        cdf.push(1.0);
+
+        Self { cdf: cdf.into() }
+    }
+
+    /// The real probability that [`crate::random_generator::event_occurred`] returns `true`
+    /// for a given `probability`.
+    ///
+    /// Random `f32`s are generated as multiples of `2^-24`,
+    /// so the comparison against the probability is slightly biased upwards.
+    fn hazard(probability: f32) -> f64 {
+        const STEPS: f64 = (1u32 << 24) as f64;
+        ((probability as f64 * STEPS).ceil() / STEPS).clamp(0.0, 1.0)
+    }
+
+    pub fn sample<T: rand::Rng>(&self, rng: &mut T) -> u32 {
+        let rand: f64 = rng.random();
+        // The first duration whose cumulative probability is greater than the random number
+        (self.cdf.partition_point(|&p| p <= rand) + 1) as u32
+    }
+
+    pub fn mean(&self) -> f32 {
+        // The sum of the probabilities of still being parked after each duration
+        let still_parked = self.cdf.iter().map(|p| 1.0 - p).sum::<f64>();
+        (1.0 + still_parked) as f32
+    }
+
+    pub fn max(&self) -> u32 {
+        self.cdf.len() as u32
+    }
+}
+
+/// The distribution used to generate stay durations for the continuous simulators.
+#[derive(Clone)]
+pub enum StaySampler {
+    /// The (correct) triangular distribution.
+    Triangular(TriangularPdfSampler),
+    /// The distribution implied by the assignment's discrete departure rule.
+    Implied(ImpliedStaySampler),
+    /// An exponential distribution with this mean, which makes the lot an M/M/c queue
+    /// whose results can be checked against the analytical formulas (see [`crate::analytic`]).
+    Exponential(f32),
+}
+
+impl StaySampler {
+    /// Stays are cut off at this many times the mean of an exponential distribution,
+    /// which only affects about one stay in 10^13.
+    const EXPONENTIAL_CUTOFF: f32 = 30.0;
+
+    /// Creates the stay distribution for a given max stay.
+    /// If `skew` is set, durations match the discrete (assignment) simulators instead.
+    pub fn new(max_time: u32, skew: bool) -> Self {
+        if skew {
+            Self::Implied(ImpliedStaySampler::new(max_time))
+        } else {
+            Self::Triangular(TriangularPdfSampler::new(
+                0.0,
+                (max_time / 2) as f32,
+                max_time as f32,
+            ))
+        }
+    }
+
+    #[inline]
+    pub fn sample<T: rand::Rng>(&self, rng: &mut T) -> u32 {
+        match self {
+            Self::Triangular(pdf) => pdf.sample(rng),
+            Self::Implied(pdf) => pdf.sample(rng),
+            Self::Exponential(mean) => (random_generator::exponential(rng, *mean)
+                .min(mean * Self::EXPONENTIAL_CUTOFF))
+            .round() as u32,
+        }
+    }
+
+    /// The mean stay duration.
+    pub fn mean(&self) -> f32 {
+        match self {
+            Self::Triangular(pdf) => pdf.mean(),
+            Self::Implied(pdf) => pdf.mean(),
+            Self::Exponential(mean) => *mean,
+        }
+    }
+
+    /// The longest possible stay duration.
+    pub fn max(&self) -> u32 {
+        match self {
+            Self::Triangular(pdf) => pdf.max(),
+            Self::Implied(pdf) => pdf.max(),
+            Self::Exponential(mean) => (mean * Self::EXPONENTIAL_CUTOFF).round() as u32,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{SeedableRng, rngs::StdRng};
+
+    use super::*;
+
+    #[test]
+    fn implied_cdf_is_the_product_of_the_hazards() {
+        // For a max stay of 4, the PDF is 1/4, 1/2 and 1/4 after 1, 2 and 3 ticks,
+        // and every car that is still parked after that leaves at 4
+        let sampler = ImpliedStaySampler::new(4);
+        let survival = [0.75, 0.75 * 0.5, 0.75 * 0.5 * 0.75, 0.0];
+        assert_eq!(sampler.cdf.len(), survival.len());
+        for (p, still_parked) in sampler.cdf.iter().zip(survival) {
+            assert!((p - (1.0 - still_parked)).abs() < 1e-12, "cdf {p}");
+        }
+        assert!((sampler.mean() - (1.0 + 0.75 + 0.375 + 0.281_25)).abs() < 1e-6);
+    }
+
+    #[test]
+    fn implied_stays_match_the_discrete_departure_rule() {
+        const MAX: u32 = 60;
+        const CARS: usize = 200_000;
+        let pdf = TriangularPdf::new(0, MAX / 2, MAX);
+        let sampler = ImpliedStaySampler::new(MAX);
+        let mut rng = StdRng::seed_from_u64(1);
+
+        // The stays that the discrete simulators produce, checking each car once per tick
+        let mut discrete = [0usize; MAX as usize + 1];
+        for _ in 0..CARS {
+            let mut duration = 1;
+            while duration < MAX && !random_generator::event_occurred(&mut rng, pdf.pdf(duration)) {
+                duration += 1;
+            }
+            discrete[duration as usize] += 1;
+        }
+        let mut implied = [0usize; MAX as usize + 1];
+        for _ in 0..CARS {
+            implied[sampler.sample(&mut rng) as usize] += 1;
+        }
+
+        // Both empirical CDFs are within a few standard errors of each other at every duration
+        let (mut discrete_total, mut implied_total) = (0, 0);
+        for (d, s) in discrete.iter().zip(&implied) {
+            discrete_total += d;
+            implied_total += s;
+            let difference = discrete_total.abs_diff(implied_total) as f64 / CARS as f64;
+            assert!(difference < 0.006, "the CDFs differ by {difference}");
+        }
+    }
 }
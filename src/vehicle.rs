@@ -0,0 +1,119 @@
+use std::str::FromStr;
+
+use crate::{charging::Charging, parking_lot::UNITS_PER_SPOT};
+
+/// A type of vehicle, which determines the pools of spots that it can park in.
+#[derive(Clone)]
+pub struct VehicleType {
+    /// The fraction of arriving cars that are of this type.
+    pub share: f32,
+    /// The pools that this vehicle can park in, in order of preference.
+    pub pools: Vec<usize>,
+    /// The charging needs of this vehicle, if it is an EV.
+    pub charging: Option<Charging>,
+    /// The pools without chargers that an EV can move to once it is done charging.
+    pub move_to: Vec<usize>,
+    /// The space that this vehicle takes up, in units of space (see [`UNITS_PER_SPOT`]).
+    pub units: usize,
+}
+
+/// A vehicle type as specified on the command line.
+///
+/// The format is `NAME,share=FRACTION[,pools=POOL/POOL/...][,size=SPOTS]`,
+/// where the pools are listed in order of preference.
+/// If no pools are given, the vehicle can only park in the pool that has no fixed share or size.
+/// The size is the number of spots that the vehicle takes up, which can be a fraction
+/// (e.g. `0.25` for motorcycles sharing a spot) or more than one (e.g. `3` for a bus).
+///
+/// EVs also have `battery=KWH[,soc=MIN/MODE/MAX][,target=PERCENT][,idle=MIN/MODE/MAX][,after=leave|move]`,
+/// where the state of charge on arrival is given as a percentage and the idle time in seconds.
+#[derive(Clone)]
+pub struct VehicleSpec {
+    pub name: String,
+    pub share: f32,
+    pub pools: Vec<String>,
+    pub charging: Option<Charging>,
+    /// The space that the vehicle takes up, in units of space.
+    pub units: usize,
+}
+
+impl FromStr for VehicleSpec {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parts = s.split(',');
+        let name = parts.next().unwrap_or_default();
+        if name.is_empty() || name.contains('=') {
+            return Err("the vehicle must start with a name".into());
+        }
+
+        let mut spec = Self {
+            name: name.into(),
+            share: 0.0,
+            pools: Vec::new(),
+            charging: None,
+            units: UNITS_PER_SPOT,
+        };
+        let mut charging = Charging::new(0.0);
+        let mut charging_options = false;
+
+        for part in parts {
+            match part.split_once('=') {
+                Some(("share", value)) => {
+                    spec.share = value
+                        .parse()
+                        .map_err(|e| format!("invalid share `{value}`: {e}"))?;
+                }
+                Some(("pools", value)) => {
+                    spec.pools = value.split('/').map(String::from).collect();
+                }
+                Some(("size", value)) => match value.parse::<f32>() {
+                    Ok(size) if (size * UNITS_PER_SPOT as f32).round() >= 1.0 => {
+                        spec.units = (size * UNITS_PER_SPOT as f32).round() as usize;
+                    }
+                    _ => {
+                        return Err(format!(
+                            "invalid size `{value}` (the smallest size is 1/{UNITS_PER_SPOT} of a spot)"
+                        ));
+                    }
+                },
+                Some(("battery", value)) => match value.parse::<f32>() {
+                    Ok(battery) if battery > 0.0 => charging.battery = battery,
+                    _ => return Err(format!("invalid battery `{value}`")),
+                },
+                Some((key, value)) if charging.parse_option(key, value)? => {
+                    charging_options = true;
+                }
+                _ => return Err(format!("unknown vehicle option `{part}`")),
+            }
+        }
+
+        if spec.share <= 0.0 {
+            return Err("the vehicle needs a positive share".into());
+        }
+        if charging.battery > 0.0 {
+            spec.charging = Some(charging);
+        } else if charging_options {
+            return Err("charging options need a battery".into());
+        }
+
+        Ok(spec)
+    }
+}
+
+/// Randomly picks the type of an arriving vehicle.
+pub fn sample<T: rand::Rng>(rng: &mut T, vehicles: &[VehicleType]) -> usize {
+    if vehicles.len() == 1 {
+        return 0;
+    }
+
+    let total = vehicles.iter().map(|vehicle| vehicle.share).sum::<f32>();
+    let mut rand = rng.random::<f32>() * total;
+    for (i, vehicle) in vehicles.iter().enumerate() {
+        if rand < vehicle.share {
+            return i;
+        }
+        rand -= vehicle.share;
+    }
+    vehicles.len() - 1
+}
@@ -0,0 +1,111 @@
+use std::str::FromStr;
+
+use rayon::prelude::*;
+
+use crate::{
+    cli::Cli, event_simulator::EventSimulator, random_generator, scenario::Scenario,
+    simulator::Simulator,
+};
+
+/// The number of seconds between samples of the occupancy when detecting the warm-up period.
+const TRACE_INTERVAL: u32 = 60;
+/// How close the smoothed occupancy has to get to its steady-state value,
+/// as a fraction of that value, for the warm-up period to be considered over.
+const TOLERANCE: f64 = 0.02;
+
+/// How full the lot is at the start of each run.
+#[derive(Clone, Copy)]
+pub enum InitialOccupancy {
+    /// A fixed fraction of the spots are taken.
+    Fraction(f32),
+    /// The number of parked cars is drawn from the steady state of the demand,
+    /// as if the lot had infinite capacity. Any cars that don't fit start in the queue.
+    Steady,
+}
+
+impl FromStr for InitialOccupancy {
+    type Err = String;
+
+    /// Parses either a fraction of the capacity, or `steady`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s == "steady" {
+            return Ok(Self::Steady);
+        }
+        match s.parse::<f32>() {
+            Ok(fraction) if (0.0..=1.0).contains(&fraction) => Ok(Self::Fraction(fraction)),
+            _ => Err(format!(
+                "`{s}` must be a fraction between 0 and 1, or `steady`"
+            )),
+        }
+    }
+}
+
+/// The period at the start of each run whose statistics are discarded.
+#[derive(Clone, Copy)]
+pub enum WarmUp {
+    Fixed(u32),
+    /// The warm-up period is detected using Welch's method.
+    Auto,
+}
+
+impl FromStr for WarmUp {
+    type Err = String;
+
+    /// Parses either a number of seconds, or `auto`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s == "auto" {
+            return Ok(Self::Auto);
+        }
+        s.parse()
+            .map(Self::Fixed)
+            .map_err(|e| format!("invalid warm-up period `{s}`: {e}"))
+    }
+}
+
+/// Detects the warm-up period using Welch's method.
+///
+/// The occupancy of a lot with unlimited capacity (i.e. the demand for spots) is traced over every run
+/// and averaged across the runs, then smoothed with a moving average.
+/// The warm-up period ends once the smoothed occupancy first gets close to its steady-state value,
+/// which is estimated from the second half of the simulation.
+///
+/// This only makes sense for steady-state studies, since demand that changes with the time of day
+/// never settles down.
+pub fn detect(cli: &Cli, scenario: &Scenario) -> u32 {
+    let traces = (1..=cli.runs)
+        .into_par_iter()
+        .map(|run| {
+            let rng = &mut random_generator::run_rng(cli.seed, usize::MAX, run);
+            let mut sim = EventSimulator::new(usize::MAX, scenario, rng);
+            sim.trace_occupancy(TRACE_INTERVAL);
+            sim.simulate(rng);
+            sim.occupancy_trace().to_vec()
+        })
+        .collect::<Vec<_>>();
+
+    let len = traces.iter().map(Vec::len).min().unwrap_or_default();
+    if len == 0 {
+        return 0;
+    }
+    let average = (0..len)
+        .map(|i| traces.iter().map(|trace| trace[i] as f64).sum::<f64>() / traces.len() as f64)
+        .collect::<Vec<_>>();
+
+    // Welch's moving average, where the window shrinks near the start so that it stays centred
+    let window = (len / 50).max(1);
+    let smoothed = (0..len)
+        .map(|i| {
+            let half = window.min(i).min(len - 1 - i);
+            let values = &average[i - half..=i + half];
+            values.iter().sum::<f64>() / values.len() as f64
+        })
+        .collect::<Vec<_>>();
+
+    let steady = smoothed[len / 2..].iter().sum::<f64>() / (len - len / 2) as f64;
+    let end = smoothed
+        .iter()
+        .position(|&value| (value - steady).abs() <= TOLERANCE * steady.max(1.0))
+        .unwrap_or_default();
+
+    end as u32 * TRACE_INTERVAL
+}